@@ -1,5 +1,5 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{self, Mint, Token, TokenAccount, Transfer};
+use anchor_spl::token::{self, CloseAccount, Mint, Token, TokenAccount, Transfer};
 
 declare_id!("5V4D1b9wrjuJC3aAtNbayVgMYt5879w2rL2k5UoQGTvM");
 
@@ -21,6 +21,104 @@ pub mod energy_auction {
         state.version = version;
         state.quote_mint = ctx.accounts.quote_mint.key();
         state.fee_vault = ctx.accounts.fee_vault.key();
+        state.shutdown = false;
+        state.current_round = 0;
+        state.pending_authority = None;
+        state.min_auction_duration = 0;
+
+        Ok(())
+    }
+
+    /// Read-only: flat view of the protocol configuration, decoupled from the GlobalState layout
+    pub fn get_protocol_config(ctx: Context<GetProtocolConfig>) -> Result<ProtocolConfig> {
+        let state = &ctx.accounts.global_state;
+        Ok(ProtocolConfig {
+            authority: state.authority,
+            pending_authority: state.pending_authority,
+            fee_bps: state.fee_bps,
+            version: state.version,
+            quote_mint: state.quote_mint,
+            fee_vault: state.fee_vault,
+            shutdown: state.shutdown,
+            current_round: state.current_round,
+            min_auction_duration: state.min_auction_duration,
+        })
+    }
+
+    /// Step 1 of an authority handoff: current authority nominates its successor
+    pub fn propose_authority_transfer(
+        ctx: Context<ProposeAuthorityTransfer>,
+        new_authority: Pubkey,
+    ) -> Result<()> {
+        let state = &mut ctx.accounts.global_state;
+        require_keys_eq!(
+            state.authority,
+            ctx.accounts.authority.key(),
+            EnergyAuctionError::InvalidAuthority
+        );
+        state.pending_authority = Some(new_authority);
+
+        emit!(AuthorityTransferProposed {
+            current_authority: state.authority,
+            proposed_authority: new_authority,
+        });
+
+        Ok(())
+    }
+
+    /// Step 2 of an authority handoff: the nominee accepts, proving it controls the key
+    pub fn accept_authority_transfer(ctx: Context<AcceptAuthorityTransfer>) -> Result<()> {
+        let state = &mut ctx.accounts.global_state;
+        let new_authority = ctx.accounts.new_authority.key();
+        require!(
+            state.pending_authority == Some(new_authority),
+            EnergyAuctionError::Unauthorized
+        );
+
+        let previous_authority = state.authority;
+        state.authority = new_authority;
+        state.pending_authority = None;
+
+        emit!(AuthorityTransferAccepted {
+            previous_authority,
+            new_authority,
+        });
+
+        Ok(())
+    }
+
+    /// Set the minimum time (seconds) a timeslot must stay Open before it can be sealed
+    pub fn set_min_auction_duration(
+        ctx: Context<SetMinAuctionDuration>,
+        min_auction_duration: i64,
+    ) -> Result<()> {
+        let state = &mut ctx.accounts.global_state;
+        require_keys_eq!(
+            state.authority,
+            ctx.accounts.authority.key(),
+            EnergyAuctionError::InvalidAuthority
+        );
+        require!(min_auction_duration >= 0, EnergyAuctionError::ConstraintViolation);
+        state.min_auction_duration = min_auction_duration;
+        Ok(())
+    }
+
+    /// Permanently halt new auctions, supply and bids. Settlement, refund and
+    /// withdrawal paths stay open so participants can recover funds.
+    /// There is no instruction to undo this.
+    pub fn protocol_shutdown(ctx: Context<ProtocolShutdown>) -> Result<()> {
+        let state = &mut ctx.accounts.global_state;
+        require_keys_eq!(
+            state.authority,
+            ctx.accounts.authority.key(),
+            EnergyAuctionError::InvalidAuthority
+        );
+        require!(!state.shutdown, EnergyAuctionError::ProtocolShutdown);
+        state.shutdown = true;
+
+        emit!(ProtocolShutdownActivated {
+            authority: state.authority,
+        });
 
         Ok(())
     }
@@ -31,6 +129,9 @@ pub mod energy_auction {
         epoch_ts: i64,
         lot_size: u64,
         price_tick: u64,
+        metadata_uri: [u8; 128],
+        min_bid_increment: u64,
+        limits: TimeslotLimits,
     ) -> Result<()> {
         // only protocol authority may open
         require_keys_eq!(
@@ -38,8 +139,18 @@ pub mod energy_auction {
             ctx.accounts.authority.key(),
             EnergyAuctionError::InvalidAuthority
         );
+        require!(!ctx.accounts.global_state.shutdown, EnergyAuctionError::ProtocolShutdown);
         require!(lot_size > 0, EnergyAuctionError::ConstraintViolation);
         require!(price_tick > 0, EnergyAuctionError::ConstraintViolation);
+        require!(min_bid_increment.is_multiple_of(price_tick), EnergyAuctionError::PrecisionError);
+        require!(
+            limits.min_reserve_price.is_multiple_of(price_tick) && limits.max_reserve_price.is_multiple_of(price_tick),
+            EnergyAuctionError::PrecisionError
+        );
+        require!(
+            limits.max_reserve_price == 0 || limits.min_reserve_price <= limits.max_reserve_price,
+            EnergyAuctionError::ConstraintViolation
+        );
 
         let slot = &mut ctx.accounts.timeslot;
         slot.epoch_ts = epoch_ts;
@@ -53,6 +164,59 @@ pub mod energy_auction {
         slot.tail_page = None;
         slot.clearing_price = 0;
         slot.total_sold_quantity = 0; // Initialize new field
+        slot.cleared_supply = 0;
+        slot.marginal_reserve = 0;
+        slot.marginal_supply = 0;
+        slot.marginal_sold = 0;
+        slot.energy_mint = Pubkey::default(); // set by the first commit_supply
+        slot.min_reserve_price = u64::MAX; // no supply committed yet
+        slot.metadata_uri = metadata_uri;
+        slot.min_bid_increment = min_bid_increment; // 0 = disabled
+        slot.highest_bid_price = 0;
+        slot.lowest_bid_price = u64::MAX; // no bids yet
+        slot.max_total_supply = limits.max_total_supply; // 0 = unlimited
+        slot.max_total_bids = limits.max_total_bids;     // 0 = unlimited
+        slot.min_reserve_bound = limits.min_reserve_price; // 0 = no floor
+        slot.opened_at = Clock::get()?.unix_timestamp;
+        slot.max_reserve_bound = limits.max_reserve_price; // 0 = no ceiling
+
+        // assign the next sequential round and index it for lookup by round number
+        let global_state = &mut ctx.accounts.global_state;
+        let round = global_state.current_round;
+        slot.round = round;
+        let round_index = &mut ctx.accounts.round_index;
+        round_index.timeslot = slot.key();
+        round_index.epoch_ts = epoch_ts;
+        global_state.current_round = round.checked_add(1).ok_or(EnergyAuctionError::MathError)?;
+
+        emit!(TimeslotOpened {
+            timeslot: epoch_ts as u64,
+            round,
+            opened_at: slot.opened_at,
+        });
+
+        Ok(())
+    }
+
+    /// Update the off-chain metadata pointer of an Open timeslot
+    pub fn update_timeslot_metadata(
+        ctx: Context<UpdateTimeslotMetadata>,
+        metadata_uri: [u8; 128],
+    ) -> Result<()> {
+        require_keys_eq!(
+            ctx.accounts.global_state.authority,
+            ctx.accounts.authority.key(),
+            EnergyAuctionError::InvalidAuthority
+        );
+        let ts = &mut ctx.accounts.timeslot;
+        require!(matches!(ts.status(), TimeslotStatus::Open), EnergyAuctionError::InvalidTimeslot);
+        ts.metadata_uri = metadata_uri;
+
+        emit!(TimeslotMetadataUpdated {
+            timeslot: ts.epoch_ts as u64,
+            metadata_uri,
+        });
+
         Ok(())
     }
 
@@ -64,9 +228,28 @@ pub mod energy_auction {
         reserve_price: u64,
         quantity: u64,
     ) -> Result<()> {
+        require!(!ctx.accounts.global_state.shutdown, EnergyAuctionError::ProtocolShutdown);
         require!(quantity > 0, EnergyAuctionError::ConstraintViolation);
         let ts = &mut ctx.accounts.timeslot;
         require!(matches!(ts.status(), TimeslotStatus::Open), EnergyAuctionError::InvalidTimeslot);
+        // keep the supply curve on the same grid as bids
+        require!(reserve_price.is_multiple_of(ts.price_tick), EnergyAuctionError::PrecisionError);
+        // price takers opt out of the floor; they accept whatever clears
+        require!(
+            reserve_price == Supply::PRICE_TAKER_RESERVE || reserve_price >= ts.min_reserve_bound,
+            EnergyAuctionError::ReserveOutOfBounds
+        );
+        require!(
+            ts.max_reserve_bound == 0 || reserve_price <= ts.max_reserve_bound,
+            EnergyAuctionError::ReserveOutOfBounds
+        );
+
+        // every seller in a timeslot delivers the same energy token
+        if ts.energy_mint == Pubkey::default() {
+            ts.energy_mint = ctx.accounts.energy_mint.key();
+        } else {
+            require_keys_eq!(ts.energy_mint, ctx.accounts.energy_mint.key(), EnergyAuctionError::ConstraintViolation);
+        }
 
         let supply = &mut ctx.accounts.supply;
         supply.supplier      = ctx.accounts.signer.key();
@@ -77,6 +260,14 @@ pub mod energy_auction {
         supply.energy_mint   = ctx.accounts.energy_mint.key();
         supply.escrow_vault  = ctx.accounts.seller_escrow.key();
         supply.claimed       = false;
+        supply.delivered     = 0;
+        supply.unsold_reclaimed = false;
+
+        // surface a domain error instead of the raw SPL token failure
+        require!(
+            ctx.accounts.seller_source.amount >= quantity,
+            EnergyAuctionError::InsufficientBalance
+        );
 
         // move energy tokens: seller_source -> seller_escrow (authority = signer)
         let cpi_ctx = CpiContext::new(
@@ -90,6 +281,21 @@ pub mod energy_auction {
         token::transfer(cpi_ctx, quantity)?;
 
         ts.total_supply = ts.total_supply.checked_add(quantity).ok_or(EnergyAuctionError::MathError)?;
+        require!(
+            ts.max_total_supply == 0 || ts.total_supply <= ts.max_total_supply,
+            EnergyAuctionError::SupplyCapExceeded
+        );
+        ts.min_reserve_price = ts.min_reserve_price.min(reserve_price);
+
+        // bucket the commitment into the supply curve at its reserve price
+        let level = &mut ctx.accounts.supply_level;
+        if level.timeslot == Pubkey::default() {
+            // first supply at this reserve price
+            level.timeslot = ts.key();
+            level.reserve_price = reserve_price;
+        }
+        level.total_quantity = level.total_quantity.checked_add(quantity).ok_or(EnergyAuctionError::MathError)?;
+        level.seller_count = level.seller_count.checked_add(1).ok_or(EnergyAuctionError::MathError)?;
 
         emit!(SupplyCommitted {
             supplier: supply.supplier,
@@ -100,26 +306,123 @@ pub mod energy_auction {
         Ok(())
     }
 
+    /// Seller pulls committed supply back out while the timeslot is still Open.
+    /// Returns the escrowed energy, closes the supply and escrow accounts (so the seller
+    /// may commit again) and removes the quantity from the supply curve.
+    /// Also how sellers recover their energy from a Cancelled timeslot.
+    /// min_reserve_price is a running minimum and is not raised.
+    pub fn withdraw_supply(ctx: Context<WithdrawSupply>) -> Result<()> {
+        let ts = &mut ctx.accounts.timeslot;
+        require!(
+            matches!(ts.status(), TimeslotStatus::Open | TimeslotStatus::Cancelled),
+            EnergyAuctionError::InvalidTimeslot
+        );
+        let quantity = ctx.accounts.supply.amount;
+        require!(
+            ctx.accounts.seller_escrow.amount >= quantity,
+            EnergyAuctionError::InsufficientEscrow
+        );
+
+        let timeslot_seeds = &[&b"timeslot"[..], &ts.epoch_ts.to_le_bytes(), &[ctx.bumps.timeslot]];
+        let signer_seeds = &[&timeslot_seeds[..]];
+
+        // move energy tokens: seller_escrow -> seller_destination (authority = timeslot PDA).
+        // Sweep the full balance, not just the committed amount, so stray deposits
+        // into the escrow can't block close_account below.
+        let escrow_balance = ctx.accounts.seller_escrow.amount;
+        let cpi_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            Transfer {
+                from: ctx.accounts.seller_escrow.to_account_info(),
+                to: ctx.accounts.seller_destination.to_account_info(),
+                authority: ts.to_account_info(),
+            },
+            signer_seeds,
+        );
+        token::transfer(cpi_ctx, escrow_balance)?;
+
+        // escrow is now empty; close it so a later commit_supply can re-init it
+        let cpi_ctx_close = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            CloseAccount {
+                account: ctx.accounts.seller_escrow.to_account_info(),
+                destination: ctx.accounts.seller.to_account_info(),
+                authority: ts.to_account_info(),
+            },
+            signer_seeds,
+        );
+        token::close_account(cpi_ctx_close)?;
+
+        ts.total_supply = ts.total_supply.checked_sub(quantity).ok_or(EnergyAuctionError::MathError)?;
+        let level = &mut ctx.accounts.supply_level;
+        level.total_quantity = level.total_quantity.checked_sub(quantity).ok_or(EnergyAuctionError::MathError)?;
+        level.seller_count = level.seller_count.checked_sub(1).ok_or(EnergyAuctionError::MathError)?;
+
+        Ok(())
+    }
+
 
     /// Buyer places bid, escrows quote tokens (USDC) into a program-owned vault (authority = timeslot PDA)
+    /// A non-zero `bid_nonce` rejects a retry on the same page; it is not checked across pages.
     pub fn place_bid(
         ctx: Context<PlaceBid>,
-        page_index: u32,
+        _page_index: u32,
         price: u64,
         quantity: u64,
         timestamp: i64,
+        bid_nonce: u64,
     ) -> Result<()> {
+        require!(!ctx.accounts.global_state.shutdown, EnergyAuctionError::ProtocolShutdown);
         let ts = &mut ctx.accounts.timeslot;
 
         require!(matches!(ts.status(), TimeslotStatus::Open), EnergyAuctionError::InvalidTimeslot);
         require!(price > 0 && quantity > 0, EnergyAuctionError::ConstraintViolation);
-        require!(price % ts.price_tick == 0, EnergyAuctionError::ConstraintViolation);
+        require!(price.is_multiple_of(ts.price_tick), EnergyAuctionError::ConstraintViolation);
+        // opt-in anti penny-jumping: a bid at or above the best must beat it by the increment
+        if ts.min_bid_increment > 0 && ts.highest_bid_price > 0 && price >= ts.highest_bid_price {
+            let min_price = ts
+                .highest_bid_price
+                .checked_add(ts.min_bid_increment)
+                .ok_or(EnergyAuctionError::MathError)?;
+            require!(price >= min_price, EnergyAuctionError::BidIncrementTooSmall);
+        }
+
+        // validate page linkage before any funds move
+        let page = &mut ctx.accounts.bid_page;
+        if page.bids.is_empty() && page.timeslot == Pubkey::default() {
+            // first init of this page; the Open check above keeps pages from being created later
+            page.timeslot = ts.key();
+            page.next_page = None;
+            // pages are addressed by index; head/tail just record the first and newest
+            let page_key = page.key();
+            if ts.head_page.is_none() {
+                ts.head_page = Some(page_key);
+            }
+            ts.tail_page = Some(page_key);
+        } else {
+            // page must belong to this timeslot
+            require_keys_eq!(page.timeslot, ts.key(), EnergyAuctionError::ConstraintViolation);
+        }
+        require!(page.bids.len() < BidPage::MAX_BIDS, EnergyAuctionError::ConstraintViolation);
+        // opt-in idempotency: a retried transaction must not escrow twice. The check only
+        // covers this page, so a retry must reuse the same page_index to be caught.
+        if bid_nonce != 0 {
+            let buyer = ctx.accounts.buyer.key();
+            require!(
+                !page.bids.iter().any(|b| b.owner == buyer && b.nonce == bid_nonce),
+                EnergyAuctionError::DuplicateBid
+            );
+        }
 
         // escrow amount = price * quantity
         let amount = (price as u128)
             .checked_mul(quantity as u128)
             .ok_or(EnergyAuctionError::MathError)?;
         let amount = u64::try_from(amount).map_err(|_| EnergyAuctionError::MathError)?;
+        require!(
+            ctx.accounts.buyer_source.amount >= amount,
+            EnergyAuctionError::InsufficientBalance
+        );
 
         // transfer quote to escrow
         let cpi_ctx = CpiContext::new(
@@ -134,25 +437,105 @@ pub mod energy_auction {
 
         // append to page
         let page = &mut ctx.accounts.bid_page;
-        if page.bids.is_empty() && page.timeslot == Pubkey::default() {
-            // first init of this page
-            page.timeslot = ts.key();
-            page.next_page = None;
-        } else {
-            // page must belong to this timeslot
-            require_keys_eq!(page.timeslot, ts.key(), EnergyAuctionError::ConstraintViolation);
-        }
-
-        require!(page.bids.len() < BidPage::MAX_BIDS, EnergyAuctionError::ConstraintViolation);
         page.bids.push(Bid {
             owner: ctx.accounts.buyer.key(),
             price,
             quantity,
             timestamp,
             status: BidStatus::Active as u8,
+            nonce: bid_nonce,
         });
 
+        // bucket the bid into the demand curve at its price
+        let level = &mut ctx.accounts.demand_level;
+        if level.timeslot == Pubkey::default() {
+            // first bid at this price
+            level.timeslot = ts.key();
+            level.price = price;
+        }
+        level.total_quantity = level.total_quantity.checked_add(quantity).ok_or(EnergyAuctionError::MathError)?;
+        level.bid_count = level.bid_count.checked_add(1).ok_or(EnergyAuctionError::MathError)?;
+
         ts.total_bids = ts.total_bids.checked_add(quantity).ok_or(EnergyAuctionError::MathError)?;
+        require!(
+            ts.max_total_bids == 0 || ts.total_bids <= ts.max_total_bids,
+            EnergyAuctionError::DemandCapExceeded
+        );
+        ts.highest_bid_price = ts.highest_bid_price.max(price);
+        ts.lowest_bid_price = ts.lowest_bid_price.min(price);
+        Ok(())
+    }
+
+    /// Buyer withdraws an Active bid while the timeslot is Open or Cancelled and gets the escrow back.
+    /// highest/lowest_bid_price are outer bounds and are left untouched.
+    pub fn cancel_bid(ctx: Context<CancelBid>, _page_index: u32, bid_index: u32) -> Result<()> {
+        let ts = &mut ctx.accounts.timeslot;
+        require!(
+            matches!(ts.status(), TimeslotStatus::Open | TimeslotStatus::Cancelled),
+            EnergyAuctionError::InvalidTimeslot
+        );
+
+        let page = &mut ctx.accounts.bid_page;
+        require_keys_eq!(page.timeslot, ts.key(), EnergyAuctionError::ConstraintViolation);
+        let bid = page
+            .bids
+            .get_mut(bid_index as usize)
+            .ok_or(EnergyAuctionError::ConstraintViolation)?;
+        require_keys_eq!(bid.owner, ctx.accounts.buyer.key(), EnergyAuctionError::Unauthorized);
+        require!(bid.status == BidStatus::Active as u8, EnergyAuctionError::BidNotActive);
+
+        // same escrow amount place_bid took
+        let amount = (bid.price as u128)
+            .checked_mul(bid.quantity as u128)
+            .ok_or(EnergyAuctionError::MathError)?;
+        let amount = u64::try_from(amount).map_err(|_| EnergyAuctionError::MathError)?;
+        require!(
+            ctx.accounts.timeslot_quote_escrow.amount >= amount,
+            EnergyAuctionError::InsufficientEscrow
+        );
+
+        // the demand level must be this bid's price bucket
+        let level = &mut ctx.accounts.demand_level;
+        require_keys_eq!(level.timeslot, ts.key(), EnergyAuctionError::ConstraintViolation);
+        require!(level.price == bid.price, EnergyAuctionError::ConstraintViolation);
+
+        bid.status = BidStatus::Cancelled as u8;
+        ts.total_bids = ts.total_bids.checked_sub(bid.quantity).ok_or(EnergyAuctionError::MathError)?;
+        level.total_quantity = level.total_quantity.checked_sub(bid.quantity).ok_or(EnergyAuctionError::MathError)?;
+        level.bid_count = level.bid_count.checked_sub(1).ok_or(EnergyAuctionError::MathError)?;
+
+        let timeslot_seeds = &[&b"timeslot"[..], &ts.epoch_ts.to_le_bytes(), &[ctx.bumps.timeslot]];
+        let signer_seeds = &[&timeslot_seeds[..]];
+        let cpi_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            Transfer {
+                from: ctx.accounts.timeslot_quote_escrow.to_account_info(),
+                to: ctx.accounts.buyer_quote_ata.to_account_info(),
+                authority: ts.to_account_info(),
+            },
+            signer_seeds,
+        );
+        token::transfer(cpi_ctx, amount)?;
+        Ok(())
+    }
+
+    /// Change the lot size of an Open timeslot.
+    /// lot_size is the energy unit every supply and bid quantity is counted in, so it is
+    /// immutable once any supply or bid exists. Escrow is price * quantity and does not use it.
+    pub fn set_lot_size(ctx: Context<SetLotSize>, lot_size: u64) -> Result<()> {
+        require_keys_eq!(
+            ctx.accounts.global_state.authority,
+            ctx.accounts.authority.key(),
+            EnergyAuctionError::InvalidAuthority
+        );
+        require!(lot_size > 0, EnergyAuctionError::ConstraintViolation);
+        let ts = &mut ctx.accounts.timeslot;
+        require!(matches!(ts.status(), TimeslotStatus::Open), EnergyAuctionError::InvalidTimeslot);
+        require!(
+            ts.total_supply == 0 && ts.total_bids == 0,
+            EnergyAuctionError::LotSizeLocked
+        );
+        ts.lot_size = lot_size;
         Ok(())
     }
 
@@ -165,14 +548,147 @@ pub mod energy_auction {
         );
         let ts = &mut ctx.accounts.timeslot;
         require!(matches!(ts.status(), TimeslotStatus::Open), EnergyAuctionError::InvalidTimeslot);
+        // participants get a fair bidding window before order flow freezes
+        let earliest_seal = ts
+            .opened_at
+            .checked_add(ctx.accounts.global_state.min_auction_duration)
+            .ok_or(EnergyAuctionError::MathError)?;
+        require!(
+            Clock::get()?.unix_timestamp >= earliest_seal,
+            EnergyAuctionError::AuctionTooShort
+        );
         ts.status = TimeslotStatus::Sealed as u8;
         Ok(())
     }
 
+    /// Cancel a Sealed timeslot that can never clear (no bids, or every reserve above every bid).
+    /// Buyers then get their escrow back with cancel_bid and sellers their energy with withdraw_supply.
+    pub fn cancel_timeslot(ctx: Context<CancelTimeslot>) -> Result<()> {
+        require_keys_eq!(
+            ctx.accounts.global_state.authority,
+            ctx.accounts.authority.key(),
+            EnergyAuctionError::InvalidAuthority
+        );
+        let ts = &mut ctx.accounts.timeslot;
+        require!(matches!(ts.status(), TimeslotStatus::Sealed), EnergyAuctionError::InvalidTimeslot);
+        require!(
+            ts.total_bids == 0 || !reserve_met(ts.min_reserve_price, ts.highest_bid_price),
+            EnergyAuctionError::TimeslotClearable
+        );
+        ts.status = TimeslotStatus::Cancelled as u8;
+        Ok(())
+    }
+
+    /// Read-only: timeslot state with the status decoded, for a stable client-facing schema
+    pub fn get_auction_state(ctx: Context<GetAuctionState>) -> Result<AuctionStateView> {
+        let ts = &ctx.accounts.timeslot;
+        Ok(AuctionStateView {
+            status: ts.status(),
+            epoch_ts: ts.epoch_ts,
+            lot_size: ts.lot_size,
+            price_tick: ts.price_tick,
+            total_supply: ts.total_supply,
+            total_bids: ts.total_bids,
+            clearing_price: ts.clearing_price,
+            total_sold_quantity: ts.total_sold_quantity,
+            min_reserve_price: ts.min_reserve_price,
+            highest_bid_price: ts.highest_bid_price,
+            lowest_bid_price: ts.lowest_bid_price,
+            round: ts.round,
+            opened_at: ts.opened_at,
+            min_bid_increment: ts.min_bid_increment,
+            max_total_supply: ts.max_total_supply,
+            max_total_bids: ts.max_total_bids,
+            min_reserve_bound: ts.min_reserve_bound,
+            max_reserve_bound: ts.max_reserve_bound,
+            cleared_supply: ts.cleared_supply,
+            marginal_reserve: ts.marginal_reserve,
+            marginal_supply: ts.marginal_supply,
+            marginal_sold: ts.marginal_sold,
+        })
+    }
+
+    /// Read-only: the next lifecycle step a keeper should take, and what (if anything) blocks it
+    pub fn get_next_action(ctx: Context<GetNextAction>) -> Result<NextActionView> {
+        let ts = &ctx.accounts.timeslot;
+        let view = match ts.status() {
+            TimeslotStatus::Pending | TimeslotStatus::Open if ts.total_bids == 0 => NextActionView {
+                action: NextAction::NeedsBids,
+                blocker: ActionBlocker::None,
+            },
+            TimeslotStatus::Pending | TimeslotStatus::Open => {
+                let earliest_seal = ts
+                    .opened_at
+                    .checked_add(ctx.accounts.global_state.min_auction_duration)
+                    .ok_or(EnergyAuctionError::MathError)?;
+                let blocker = if Clock::get()?.unix_timestamp < earliest_seal {
+                    ActionBlocker::AuctionTooShort
+                } else {
+                    ActionBlocker::None
+                };
+                NextActionView { action: NextAction::ReadyToSeal, blocker }
+            }
+            TimeslotStatus::Sealed => {
+                let blocker = if ts.total_bids == 0 {
+                    ActionBlocker::NoMarketClearing
+                } else if !reserve_met(ts.min_reserve_price, ts.highest_bid_price) {
+                    ActionBlocker::NoIntersection
+                } else {
+                    ActionBlocker::None
+                };
+                NextActionView { action: NextAction::ReadyToClear, blocker }
+            }
+            TimeslotStatus::Settled => NextActionView {
+                action: NextAction::Complete,
+                blocker: ActionBlocker::None,
+            },
+            TimeslotStatus::Cancelled => NextActionView {
+                action: NextAction::NeedsRefund,
+                blocker: ActionBlocker::None,
+            },
+        };
+        Ok(view)
+    }
+
+    /// Emit the full current timeslot state without mutating anything, so indexers can resync
+    pub fn emit_timeslot_snapshot(ctx: Context<EmitTimeslotSnapshot>) -> Result<()> {
+        let ts = &ctx.accounts.timeslot;
+        emit!(TimeslotSnapshot {
+            timeslot: ts.epoch_ts as u64,
+            round: ts.round,
+            status: ts.status,
+            opened_at: ts.opened_at,
+            lot_size: ts.lot_size,
+            price_tick: ts.price_tick,
+            total_supply: ts.total_supply,
+            total_bids: ts.total_bids,
+            clearing_price: ts.clearing_price,
+            total_sold_quantity: ts.total_sold_quantity,
+            min_reserve_price: ts.min_reserve_price,
+            highest_bid_price: ts.highest_bid_price,
+            lowest_bid_price: ts.lowest_bid_price,
+            min_bid_increment: ts.min_bid_increment,
+            max_total_supply: ts.max_total_supply,
+            max_total_bids: ts.max_total_bids,
+            min_reserve_bound: ts.min_reserve_bound,
+            max_reserve_bound: ts.max_reserve_bound,
+            cleared_supply: ts.cleared_supply,
+            marginal_reserve: ts.marginal_reserve,
+            marginal_supply: ts.marginal_supply,
+            marginal_sold: ts.marginal_sold,
+            quote_mint: ts.quote_mint,
+            energy_mint: ts.energy_mint,
+            metadata_uri: ts.metadata_uri,
+        });
+        Ok(())
+    }
+
     // --- SETTLEMENT FLOW ---
 
     /// 1. Settle Timeslot: Authority sets the final clearing price and sold quantity.
     /// This instruction only records the outcome; it does not move funds.
+    /// remaining_accounts: every SupplyLevelAggregate of this timeslot (any order), used
+    /// to allocate the sold quantity across sellers in merit order.
     pub fn settle_timeslot(
         ctx: Context<SettleTimeslot>,
         clearing_price: u64,
@@ -185,14 +701,106 @@ pub mod energy_auction {
         );
         let ts = &mut ctx.accounts.timeslot;
         require!(matches!(ts.status(), TimeslotStatus::Sealed), EnergyAuctionError::InvalidTimeslot);
+        // an empty order book has no market to clear
+        require!(ts.total_bids > 0, EnergyAuctionError::NoMarketClearing);
         require!(clearing_price > 0, EnergyAuctionError::ConstraintViolation);
+        // bids and reserves are grid-aligned, so a valid clearing price must be too
+        require!(clearing_price.is_multiple_of(ts.price_tick), EnergyAuctionError::PrecisionError);
         require!(total_sold_quantity <= ts.total_supply, EnergyAuctionError::MathError);
+        // cannot sell more than buyers ever bid for
+        require!(total_sold_quantity <= ts.total_bids, EnergyAuctionError::MathError);
+        // supply and demand curves must cross: some seller accepts what some buyer pays
+        require!(
+            reserve_met(ts.min_reserve_price, ts.highest_bid_price),
+            EnergyAuctionError::NoIntersection
+        );
+        require!(
+            reserve_met(ts.min_reserve_price, clearing_price)
+                && bid_clears(ts.highest_bid_price, clearing_price),
+            EnergyAuctionError::ConstraintViolation
+        );
 
         // Update timeslot state with the auction outcome
         ts.clearing_price = clearing_price;
         ts.total_sold_quantity = total_sold_quantity;
+        let (supply, supply_total) = read_levels::<SupplyLevelAggregate>(ctx.remaining_accounts, ts.key(), |l| {
+            (l.timeslot, l.reserve_price, l.total_quantity)
+        })?;
+        require!(supply_total == ts.total_supply, EnergyAuctionError::IncompleteOrderBook);
+        let fill = merit_order_fill(&supply, clearing_price, total_sold_quantity)?;
+        ts.record_fill(&fill);
+        ts.status = TimeslotStatus::Settled as u8;
+
+        Ok(())
+    }
+
+    /// 1b. Execute Auction Clearing: derive the uniform clearing price on-chain.
+    ///
+    /// remaining_accounts ordering:
+    /// - first `supply_level_count` SupplyLevelAggregate accounts of this timeslot (any order),
+    /// - then the DemandLevelAggregate accounts of this timeslot (any order).
+    ///
+    /// Both sets must be complete: supply levels must add up to `total_supply`, and
+    /// demand levels must add up to `total_bids`.
+    pub fn execute_auction_clearing(
+        ctx: Context<ExecuteAuctionClearing>,
+        supply_level_count: u8,
+    ) -> Result<()> {
+        require_keys_eq!(
+            ctx.accounts.global_state.authority,
+            ctx.accounts.authority.key(),
+            EnergyAuctionError::InvalidAuthority
+        );
+        let ts = &mut ctx.accounts.timeslot;
+        require!(matches!(ts.status(), TimeslotStatus::Sealed), EnergyAuctionError::InvalidTimeslot);
+        require!(ts.total_bids > 0, EnergyAuctionError::NoMarketClearing);
+
+        let timeslot_key = ts.key();
+        let level_count = supply_level_count as usize;
+        require!(
+            level_count <= ctx.remaining_accounts.len(),
+            EnergyAuctionError::ConstraintViolation
+        );
+
+        let (supply_accounts, demand_accounts) = ctx.remaining_accounts.split_at(level_count);
+        let (supply, supply_total) = read_levels::<SupplyLevelAggregate>(supply_accounts, timeslot_key, |l| {
+            (l.timeslot, l.reserve_price, l.total_quantity)
+        })?;
+        let (mut demand, demand_total) = read_levels::<DemandLevelAggregate>(demand_accounts, timeslot_key, |l| {
+            (l.timeslot, l.price, l.total_quantity)
+        })?;
+
+        require!(
+            supply_total == ts.total_supply && demand_total == ts.total_bids,
+            EnergyAuctionError::IncompleteOrderBook
+        );
+        require!(
+            reserve_met(ts.min_reserve_price, ts.highest_bid_price),
+            EnergyAuctionError::NoIntersection
+        );
+
+        // demand curve: one entry per price level, highest price first; levels emptied
+        // by cancellations carry no demand
+        demand.retain(|&(_, quantity)| quantity > 0);
+        demand.sort_unstable_by_key(|&(price, _)| std::cmp::Reverse(price));
+
+        let (clearing_price, cleared_quantity) =
+            compute_uniform_clearing(&demand, &supply)?.ok_or(EnergyAuctionError::NoMarketClearing)?;
+        require!(clearing_price.is_multiple_of(ts.price_tick), EnergyAuctionError::PrecisionError);
+
+        let fill = merit_order_fill(&supply, clearing_price, cleared_quantity)?;
+
+        ts.clearing_price = clearing_price;
+        ts.total_sold_quantity = cleared_quantity;
+        ts.record_fill(&fill);
         ts.status = TimeslotStatus::Settled as u8;
 
+        emit!(AuctionCleared {
+            timeslot: ts.epoch_ts as u64,
+            clearing_price,
+            total_cleared_quantity: cleared_quantity,
+        });
+
         Ok(())
     }
 
@@ -215,6 +823,7 @@ pub mod energy_auction {
         receipt.quantity = quantity;
         receipt.clearing_price = ts.clearing_price;
         receipt.redeemed = false;
+        receipt.delivered = 0;
 
         Ok(())
     }
@@ -227,23 +836,19 @@ pub mod energy_auction {
         let global_state = &ctx.accounts.global_state;
         require!(matches!(ts.status(), TimeslotStatus::Settled), EnergyAuctionError::InvalidTimeslot);
         require!(!supply.claimed, EnergyAuctionError::AlreadyClaimed);
+        require!(
+            reserve_met(supply.reserve_price, ts.clearing_price),
+            EnergyAuctionError::ReserveNotMet
+        );
 
-        // Calculate gross proceeds based on the actual sold quantity.
-        // NOTE: This assumes a single seller for the MVP.
-        let gross_proceeds = (ts.total_sold_quantity as u128)
-            .checked_mul(ts.clearing_price as u128)
-            .ok_or(EnergyAuctionError::MathError)?;
-
-        // Calculate protocol fee from the gross proceeds
-        let protocol_fee = gross_proceeds
-            .checked_mul(global_state.fee_bps as u128)
-            .ok_or(EnergyAuctionError::MathError)?
-            .checked_div(10000)
-            .ok_or(EnergyAuctionError::MathError)?;
-        
-        let net_proceeds = gross_proceeds
-            .checked_sub(protocol_fee)
-            .ok_or(EnergyAuctionError::MathError)?;
+        // Gross proceeds on this seller's pro-rata share, minus the protocol fee
+        let proceeds = seller_proceeds(ts, supply, global_state.fee_bps)?;
+        let protocol_fee = proceeds.fee;
+        let net_proceeds = proceeds.net;
+        require!(
+            ctx.accounts.timeslot_quote_escrow.amount >= proceeds.gross,
+            EnergyAuctionError::InsufficientEscrow
+        );
 
         // PDA signer seeds
         let seeds = &[&b"timeslot"[..], &ts.epoch_ts.to_le_bytes(), &[ctx.bumps.timeslot]];
@@ -259,7 +864,7 @@ pub mod energy_auction {
             },
             signer_seeds,
         );
-        token::transfer(cpi_ctx_fee, protocol_fee as u64)?;
+        token::transfer(cpi_ctx_fee, protocol_fee)?;
         
         // Transfer net proceeds to the seller
         let cpi_ctx_proceeds = CpiContext::new_with_signer(
@@ -271,13 +876,28 @@ pub mod energy_auction {
             },
             signer_seeds,
         );
-        token::transfer(cpi_ctx_proceeds, net_proceeds as u64)?;
+        token::transfer(cpi_ctx_proceeds, net_proceeds)?;
 
         supply.claimed = true;
         Ok(())
     }
 
+    /// Read-only: what withdraw_proceeds would pay this seller, and the fee it would deduct
+    pub fn preview_proceeds(ctx: Context<PreviewProceeds>) -> Result<ProceedsPreview> {
+        let ts = &ctx.accounts.timeslot;
+        let supply = &ctx.accounts.supply;
+        require!(matches!(ts.status(), TimeslotStatus::Settled), EnergyAuctionError::InvalidTimeslot);
+        require!(
+            reserve_met(supply.reserve_price, ts.clearing_price),
+            EnergyAuctionError::ReserveNotMet
+        );
+        seller_proceeds(ts, supply, ctx.accounts.global_state.fee_bps)
+    }
+
     /// 4. Redeem Energy & Refund: Buyer claims their won energy and gets a refund for over-bids.
+    /// Each call draws from one seller whose reserve was met, up to what is left of that seller's
+    /// allocation, so a fill spanning several sellers is redeemed over several calls. The refund is
+    /// paid on the first call only.
     pub fn redeem_energy_and_refund(
         ctx: Context<RedeemEnergyAndRefund>,
         total_bid_amount_escrowed: u64,
@@ -288,13 +908,37 @@ pub mod energy_auction {
         require!(!receipt.redeemed, EnergyAuctionError::AlreadyClaimed);
         require_keys_eq!(receipt.buyer, ctx.accounts.buyer.key(), EnergyAuctionError::Unauthorized);
 
+        let supply = &mut ctx.accounts.supply;
+        require!(reserve_met(supply.reserve_price, ts.clearing_price), EnergyAuctionError::ReserveNotMet);
+        let allocation = ts.seller_allocation(supply.reserve_price, supply.amount)?;
+        let available = allocation
+            .checked_sub(supply.delivered)
+            .ok_or(EnergyAuctionError::MathError)?;
+        let outstanding = receipt.quantity
+            .checked_sub(receipt.delivered)
+            .ok_or(EnergyAuctionError::MathError)?;
+        let quantity = available.min(outstanding);
+        require!(quantity > 0, EnergyAuctionError::AllocationExhausted);
+
         // A. Calculate refund
         let cost = (receipt.quantity as u128)
             .checked_mul(receipt.clearing_price as u128)
             .ok_or(EnergyAuctionError::MathError)?;
-        let refund_amount = (total_bid_amount_escrowed as u128)
-            .checked_sub(cost)
-            .ok_or(EnergyAuctionError::MathError)?;
+        let refund_amount = if receipt.delivered == 0 {
+            (total_bid_amount_escrowed as u128)
+                .checked_sub(cost)
+                .ok_or(EnergyAuctionError::MathError)?
+        } else {
+            0
+        };
+        require!(
+            ctx.accounts.timeslot_quote_escrow.amount as u128 >= refund_amount,
+            EnergyAuctionError::InsufficientEscrow
+        );
+        require!(
+            ctx.accounts.seller_escrow.amount >= quantity,
+            EnergyAuctionError::InsufficientEscrow
+        );
 
         let timeslot_seeds = &[&b"timeslot"[..], &ts.epoch_ts.to_le_bytes(), &[ctx.bumps.timeslot]];
         let signer_seeds = &[&timeslot_seeds[..]];
@@ -313,7 +957,7 @@ pub mod energy_auction {
             token::transfer(cpi_ctx, refund_amount as u64)?;
         }
 
-        // C. Transfer energy from seller escrows to buyer
+        // C. Transfer energy from the seller escrow to buyer
         let cpi_ctx_energy = CpiContext::new_with_signer(
             ctx.accounts.token_program.to_account_info(),
             Transfer {
@@ -323,9 +967,56 @@ pub mod energy_auction {
             },
             signer_seeds,
         );
-        token::transfer(cpi_ctx_energy, receipt.quantity)?;
+        token::transfer(cpi_ctx_energy, quantity)?;
+
+        supply.delivered = supply.delivered
+            .checked_add(quantity)
+            .ok_or(EnergyAuctionError::MathError)?;
+        receipt.delivered = receipt.delivered
+            .checked_add(quantity)
+            .ok_or(EnergyAuctionError::MathError)?;
+        receipt.redeemed = receipt.delivered == receipt.quantity;
+        emit!(FillReceiptRedeemed {
+            buyer: receipt.buyer,
+            timeslot: ts.epoch_ts as u64,
+            quantity,
+            refund: refund_amount as u64,
+        });
+        Ok(())
+    }
+
+    /// 5. Reclaim Unsold Energy: Seller takes back the lots of a settled timeslot that were
+    /// not allocated to buyers (everything when the reserve was not met).
+    pub fn reclaim_unsold_energy(ctx: Context<ReclaimUnsoldEnergy>) -> Result<()> {
+        let ts = &ctx.accounts.timeslot;
+        require!(matches!(ts.status(), TimeslotStatus::Settled), EnergyAuctionError::InvalidTimeslot);
+        let supply = &mut ctx.accounts.supply;
+        require!(!supply.unsold_reclaimed, EnergyAuctionError::AlreadyClaimed);
+
+        let allocation = ts.seller_allocation(supply.reserve_price, supply.amount)?;
+        let unsold = supply.amount
+            .checked_sub(allocation)
+            .ok_or(EnergyAuctionError::MathError)?;
+        require!(unsold > 0, EnergyAuctionError::NothingToReclaim);
+        require!(
+            ctx.accounts.seller_escrow.amount >= unsold,
+            EnergyAuctionError::InsufficientEscrow
+        );
+
+        let timeslot_seeds = &[&b"timeslot"[..], &ts.epoch_ts.to_le_bytes(), &[ctx.bumps.timeslot]];
+        let signer_seeds = &[&timeslot_seeds[..]];
+        let cpi_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            Transfer {
+                from: ctx.accounts.seller_escrow.to_account_info(),
+                to: ctx.accounts.seller_destination.to_account_info(),
+                authority: ts.to_account_info(),
+            },
+            signer_seeds,
+        );
+        token::transfer(cpi_ctx, unsold)?;
 
-        receipt.redeemed = true;
+        supply.unsold_reclaimed = true;
         Ok(())
     }
 }
@@ -364,7 +1055,60 @@ pub struct InitGlobalState<'info> {
     pub token_program: Program<'info, Token>,
 }
 
-/// OpenTimeslot: creates a timeslot PDA
+#[derive(Accounts)]
+pub struct GetProtocolConfig<'info> {
+    #[account(
+        seeds = [b"global_state"],
+        bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+}
+
+#[derive(Accounts)]
+pub struct ProposeAuthorityTransfer<'info> {
+    #[account(
+        mut,
+        seeds = [b"global_state"],
+        bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptAuthorityTransfer<'info> {
+    #[account(
+        mut,
+        seeds = [b"global_state"],
+        bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+    pub new_authority: Signer<'info>, // must equal global_state.pending_authority
+}
+
+#[derive(Accounts)]
+pub struct SetMinAuctionDuration<'info> {
+    #[account(
+        mut,
+        seeds = [b"global_state"],
+        bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ProtocolShutdown<'info> {
+    #[account(
+        mut,
+        seeds = [b"global_state"],
+        bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+    pub authority: Signer<'info>,
+}
+
+/// OpenTimeslot: creates a timeslot PDA
 #[derive(Accounts)]
 #[instruction(epoch_ts: i64)]
 pub struct OpenTimeslot<'info> {
@@ -380,6 +1124,15 @@ pub struct OpenTimeslot<'info> {
     )]
     pub timeslot: Account<'info, Timeslot>,
 
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + TimeslotRound::LEN,
+        seeds = [b"timeslot_round".as_ref(), global_state.current_round.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub round_index: Account<'info, TimeslotRound>,
+
     #[account(mut)]
     pub authority: Signer<'info>, // must equal global_state.authority
 
@@ -388,7 +1141,7 @@ pub struct OpenTimeslot<'info> {
 
 /// Seller commits supply for a specific timeslot (one-time)
 #[derive(Accounts)]
-#[instruction(timeslot_epoch: i64)]
+#[instruction(timeslot_epoch: i64, reserve_price: u64)]
 pub struct CommitSupply<'info> {
     pub global_state: Account<'info, GlobalState>,
 
@@ -408,6 +1161,15 @@ pub struct CommitSupply<'info> {
     )]
     pub supply: Account<'info, Supply>,
 
+    #[account(
+        init_if_needed,
+        payer = signer,
+        space = 8 + SupplyLevelAggregate::LEN,
+        seeds = [b"supply_level", timeslot.key().as_ref(), &reserve_price.to_le_bytes()],
+        bump
+    )]
+    pub supply_level: Account<'info, SupplyLevelAggregate>,
+
     pub energy_mint: Account<'info, Mint>,
 
     #[account(
@@ -434,9 +1196,93 @@ pub struct CommitSupply<'info> {
     pub token_program: Program<'info, Token>,
 }
 
+/// Seller withdraws their supply from an Open timeslot
+#[derive(Accounts)]
+pub struct WithdrawSupply<'info> {
+    #[account(
+        mut,
+        seeds = [b"timeslot", &timeslot.epoch_ts.to_le_bytes()],
+        bump
+    )]
+    pub timeslot: Account<'info, Timeslot>,
+
+    #[account(
+        mut,
+        close = seller,
+        seeds = [b"supply", timeslot.key().as_ref(), seller.key().as_ref()],
+        bump = supply.bump,
+        constraint = supply.supplier == seller.key() @ EnergyAuctionError::Unauthorized
+    )]
+    pub supply: Account<'info, Supply>,
+
+    #[account(
+        mut,
+        seeds = [b"supply_level", timeslot.key().as_ref(), &supply.reserve_price.to_le_bytes()],
+        bump
+    )]
+    pub supply_level: Account<'info, SupplyLevelAggregate>,
+
+    #[account(
+        mut,
+        seeds = [b"seller_escrow", timeslot.key().as_ref(), seller.key().as_ref()],
+        bump,
+        constraint = seller_escrow.key() == supply.escrow_vault @ EnergyAuctionError::InvalidEscrowVault
+    )]
+    pub seller_escrow: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = seller_destination.mint == supply.energy_mint @ EnergyAuctionError::ConstraintViolation,
+        constraint = seller_destination.owner == seller.key() @ EnergyAuctionError::Unauthorized
+    )]
+    pub seller_destination: Account<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub seller: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+/// Seller takes back unsold energy from a Settled timeslot
+#[derive(Accounts)]
+pub struct ReclaimUnsoldEnergy<'info> {
+    #[account(
+        seeds = [b"timeslot", &timeslot.epoch_ts.to_le_bytes()],
+        bump
+    )]
+    pub timeslot: Account<'info, Timeslot>,
+
+    #[account(
+        mut,
+        seeds = [b"supply", timeslot.key().as_ref(), seller.key().as_ref()],
+        bump = supply.bump,
+        constraint = supply.supplier == seller.key() @ EnergyAuctionError::Unauthorized
+    )]
+    pub supply: Account<'info, Supply>,
+
+    #[account(
+        mut,
+        seeds = [b"seller_escrow", timeslot.key().as_ref(), seller.key().as_ref()],
+        bump,
+        constraint = seller_escrow.key() == supply.escrow_vault @ EnergyAuctionError::InvalidEscrowVault
+    )]
+    pub seller_escrow: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = seller_destination.mint == supply.energy_mint @ EnergyAuctionError::ConstraintViolation,
+        constraint = seller_destination.owner == seller.key() @ EnergyAuctionError::Unauthorized
+    )]
+    pub seller_destination: Account<'info, TokenAccount>,
+
+    pub seller: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
+}
+
 /// Buyer places a bid into an active bid page
 #[derive(Accounts)]
-#[instruction(page_index: u32)]
+#[instruction(page_index: u32, price: u64)]
 pub struct PlaceBid<'info> {
     #[account(mut)]
     pub global_state: Account<'info, GlobalState>,
@@ -475,10 +1321,75 @@ pub struct PlaceBid<'info> {
     )]
     pub bid_page: Account<'info, BidPage>,
 
+    #[account(
+        init_if_needed,
+        payer = buyer,
+        space = 8 + DemandLevelAggregate::LEN,
+        seeds = [b"demand_level", timeslot.key().as_ref(), &price.to_le_bytes()],
+        bump
+    )]
+    pub demand_level: Account<'info, DemandLevelAggregate>,
+
     pub system_program: Program<'info, System>,
     pub token_program: Program<'info, Token>,
 }
 
+/// Buyer cancels one of their bids
+#[derive(Accounts)]
+#[instruction(page_index: u32)]
+pub struct CancelBid<'info> {
+    #[account(
+        mut,
+        seeds = [b"timeslot", &timeslot.epoch_ts.to_le_bytes()],
+        bump,
+    )]
+    pub timeslot: Account<'info, Timeslot>,
+
+    #[account(
+        mut,
+        seeds = [b"bid_page", timeslot.key().as_ref(), &page_index.to_le_bytes()],
+        bump
+    )]
+    pub bid_page: Account<'info, BidPage>,
+
+    /// Price bucket of the cancelled bid; matched against the bid in the handler
+    #[account(mut)]
+    pub demand_level: Account<'info, DemandLevelAggregate>,
+
+    #[account(
+        mut,
+        seeds = [b"quote_escrow", timeslot.key().as_ref()],
+        bump
+    )]
+    pub timeslot_quote_escrow: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = buyer_quote_ata.mint == timeslot.quote_mint @ EnergyAuctionError::ConstraintViolation,
+        constraint = buyer_quote_ata.owner == buyer.key() @ EnergyAuctionError::Unauthorized
+    )]
+    pub buyer_quote_ata: Account<'info, TokenAccount>,
+
+    pub buyer: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateTimeslotMetadata<'info> {
+    pub global_state: Account<'info, GlobalState>,
+    #[account(mut)]
+    pub timeslot: Account<'info, Timeslot>,
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetLotSize<'info> {
+    pub global_state: Account<'info, GlobalState>,
+    #[account(mut)]
+    pub timeslot: Account<'info, Timeslot>,
+    pub authority: Signer<'info>,
+}
+
 #[derive(Accounts)]
 pub struct SealTimeslot<'info> {
     pub global_state: Account<'info, GlobalState>,
@@ -487,6 +1398,34 @@ pub struct SealTimeslot<'info> {
     pub authority: Signer<'info>,
 }
 
+#[derive(Accounts)]
+pub struct CancelTimeslot<'info> {
+    pub global_state: Account<'info, GlobalState>,
+    #[account(mut)]
+    pub timeslot: Account<'info, Timeslot>,
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct GetAuctionState<'info> {
+    pub timeslot: Account<'info, Timeslot>,
+}
+
+#[derive(Accounts)]
+pub struct GetNextAction<'info> {
+    #[account(
+        seeds = [b"global_state"],
+        bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+    pub timeslot: Account<'info, Timeslot>,
+}
+
+#[derive(Accounts)]
+pub struct EmitTimeslotSnapshot<'info> {
+    pub timeslot: Account<'info, Timeslot>,
+}
+
 // --- SETTLEMENT CONTEXTS ---
 
 #[derive(Accounts)]
@@ -501,6 +1440,18 @@ pub struct SettleTimeslot<'info> {
     pub authority: Signer<'info>,
 }
 
+#[derive(Accounts)]
+pub struct ExecuteAuctionClearing<'info> {
+    pub global_state: Account<'info, GlobalState>,
+    #[account(
+        mut,
+        seeds = [b"timeslot", &timeslot.epoch_ts.to_le_bytes()],
+        bump,
+    )]
+    pub timeslot: Account<'info, Timeslot>,
+    pub authority: Signer<'info>,
+}
+
 #[derive(Accounts)]
 pub struct CreateFillReceipt<'info> {
     pub global_state: Account<'info, GlobalState>,
@@ -530,6 +1481,7 @@ pub struct WithdrawProceeds<'info> {
     #[account(
         seeds = [b"timeslot", &timeslot.epoch_ts.to_le_bytes()],
         bump,
+        constraint = timeslot.quote_mint == global_state.quote_mint @ EnergyAuctionError::ConstraintViolation
     )]
     pub timeslot: Account<'info, Timeslot>,
     #[account(
@@ -550,18 +1502,42 @@ pub struct WithdrawProceeds<'info> {
         bump
     )]
     pub fee_vault: Account<'info, TokenAccount>,
-    #[account(mut)]
+    #[account(
+        mut,
+        constraint = seller_proceeds_ata.owner == seller.key() @ EnergyAuctionError::Unauthorized,
+        constraint = seller_proceeds_ata.mint == global_state.quote_mint @ EnergyAuctionError::ConstraintViolation
+    )]
     pub seller_proceeds_ata: Account<'info, TokenAccount>,
     #[account(mut, address = supply.supplier)]
     pub seller: Signer<'info>,
     pub token_program: Program<'info, Token>,
 }
 
+#[derive(Accounts)]
+pub struct PreviewProceeds<'info> {
+    #[account(
+        seeds = [b"global_state"],
+        bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+    pub timeslot: Account<'info, Timeslot>,
+    #[account(
+        constraint = supply.timeslot == timeslot.key() @ EnergyAuctionError::ConstraintViolation
+    )]
+    pub supply: Account<'info, Supply>,
+}
+
 #[derive(Accounts)]
 pub struct RedeemEnergyAndRefund<'info> {
+    #[account(
+        seeds = [b"global_state"],
+        bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
     #[account(
         seeds = [b"timeslot", &timeslot.epoch_ts.to_le_bytes()],
         bump,
+        constraint = timeslot.quote_mint == global_state.quote_mint @ EnergyAuctionError::ConstraintViolation
     )]
     pub timeslot: Account<'info, Timeslot>,
     #[account(
@@ -577,12 +1553,28 @@ pub struct RedeemEnergyAndRefund<'info> {
         bump
     )]
     pub timeslot_quote_escrow: Account<'info, TokenAccount>,
-    #[account(mut)]
+    #[account(
+        mut,
+        constraint = buyer_quote_ata.mint == timeslot.quote_mint @ EnergyAuctionError::ConstraintViolation,
+        constraint = buyer_quote_ata.owner == buyer.key() @ EnergyAuctionError::Unauthorized
+    )]
     pub buyer_quote_ata: Account<'info, TokenAccount>,
-    #[account(mut)]
+    #[account(
+        mut,
+        constraint = buyer_energy_ata.mint == timeslot.energy_mint @ EnergyAuctionError::ConstraintViolation,
+        constraint = buyer_energy_ata.owner == buyer.key() @ EnergyAuctionError::Unauthorized
+    )]
     pub buyer_energy_ata: Account<'info, TokenAccount>,
-    /// CHECK: This is a seller's energy escrow. A real implementation would iterate over many.
-    #[account(mut)]
+    /// Seller whose allocation this redemption draws from
+    #[account(
+        mut,
+        constraint = supply.timeslot == timeslot.key() @ EnergyAuctionError::ConstraintViolation
+    )]
+    pub supply: Account<'info, Supply>,
+    #[account(
+        mut,
+        address = supply.escrow_vault @ EnergyAuctionError::InvalidEscrowVault
+    )]
     pub seller_escrow: Account<'info, TokenAccount>,
     #[account(mut)]
     pub buyer: Signer<'info>,
@@ -601,6 +1593,229 @@ pub struct SupplyCommitted {
     pub amount: u64,
 }
 
+#[event]
+pub struct TimeslotOpened {
+    pub timeslot: u64,
+    pub round: u64,
+    pub opened_at: i64,
+}
+
+#[event]
+pub struct AuthorityTransferProposed {
+    pub current_authority: Pubkey,
+    pub proposed_authority: Pubkey,
+}
+
+#[event]
+pub struct AuthorityTransferAccepted {
+    pub previous_authority: Pubkey,
+    pub new_authority: Pubkey,
+}
+
+#[event]
+pub struct TimeslotSnapshot {
+    pub timeslot: u64,
+    pub round: u64,
+    pub status: u8,
+    pub opened_at: i64,
+    pub lot_size: u64,
+    pub price_tick: u64,
+    pub total_supply: u64,
+    pub total_bids: u64,
+    pub clearing_price: u64,
+    pub total_sold_quantity: u64,
+    pub min_reserve_price: u64,
+    pub highest_bid_price: u64,
+    pub lowest_bid_price: u64,
+    pub min_bid_increment: u64,
+    pub max_total_supply: u64,
+    pub max_total_bids: u64,
+    pub min_reserve_bound: u64,
+    pub max_reserve_bound: u64,
+    pub cleared_supply: u64,
+    pub marginal_reserve: u64,
+    pub marginal_supply: u64,
+    pub marginal_sold: u64,
+    pub quote_mint: Pubkey,
+    pub energy_mint: Pubkey,
+    pub metadata_uri: [u8; 128],
+}
+
+#[event]
+pub struct FillReceiptRedeemed {
+    pub buyer: Pubkey,
+    pub timeslot: u64,
+    pub quantity: u64,
+    pub refund: u64,
+}
+
+#[event]
+pub struct AuctionCleared {
+    pub timeslot: u64,
+    pub clearing_price: u64,
+    pub total_cleared_quantity: u64,
+}
+
+#[event]
+pub struct ProtocolShutdownActivated {
+    pub authority: Pubkey,
+}
+
+#[event]
+pub struct TimeslotMetadataUpdated {
+    pub timeslot: u64,
+    pub metadata_uri: [u8; 128],
+}
+
+///////////////////////
+// Pricing
+///////////////////////
+
+// Boundary policy: the clearing price is a winning price for both sides.
+// A bid exactly at the clearing price is filled, and a reserve exactly at
+// the clearing price is accepted. Every price comparison goes through these.
+
+/// Whether a bid at `bid_price` wins at `clearing_price`
+pub fn bid_clears(bid_price: u64, clearing_price: u64) -> bool {
+    bid_price >= clearing_price
+}
+
+/// Whether a seller with `reserve_price` accepts `clearing_price`
+pub fn reserve_met(reserve_price: u64, clearing_price: u64) -> bool {
+    reserve_price <= clearing_price
+}
+
+/// Uniform-price clearing.
+///
+/// `demand` is (price, quantity) per bid price level, highest price first;
+/// `supply` is (reserve_price, quantity) per supply level, in any order.
+/// Candidate prices are the bid levels. A price is only a valid uniform price
+/// if every bid strictly above it is filled in full and supply is left over for
+/// the level itself, so only the marginal level is rationed and every bid that
+/// `bid_clears` receives some fill. Returns the lowest such price that maximises
+/// traded volume, as (clearing_price, cleared_quantity), or None if nothing trades.
+pub fn compute_uniform_clearing(
+    demand: &[(u64, u64)],
+    supply: &[(u64, u64)],
+) -> Result<Option<(u64, u64)>> {
+    let mut best: Option<(u64, u64)> = None;
+    let mut demand_above: u64 = 0;
+    for &(price, quantity) in demand {
+        let mut available: u64 = 0;
+        for &(reserve_price, level_quantity) in supply {
+            if reserve_met(reserve_price, price) {
+                available = available
+                    .checked_add(level_quantity)
+                    .ok_or(EnergyAuctionError::MathError)?;
+            }
+        }
+        // higher bids already take all supply, so this level would win nothing;
+        // supply only shrinks as the price falls, so no lower price can work either
+        if demand_above >= available {
+            break;
+        }
+        let demand_at = demand_above
+            .checked_add(quantity)
+            .ok_or(EnergyAuctionError::MathError)?;
+        let volume = demand_at.min(available);
+        let better = match best {
+            Some((_, best_volume)) => volume >= best_volume,
+            None => true,
+        };
+        if volume > 0 && better {
+            best = Some((price, volume));
+        }
+        demand_above = demand_at;
+    }
+    Ok(best)
+}
+
+/// Merit-order outcome of a settlement, stored on the timeslot by `Timeslot::record_fill`
+#[derive(Default)]
+pub struct MeritOrderFill {
+    pub cleared_supply: u64,   // lots whose reserve is met at the clearing price
+    pub marginal_reserve: u64, // reserve of the level the sale ends in
+    pub marginal_supply: u64,  // lots committed at that level
+    pub marginal_sold: u64,    // lots sold at that level
+}
+
+/// How `sold` lots at `clearing_price` fall across the supply curve. Sellers whose
+/// reserve is met fill in merit order, lowest reserve first (price takers lead): every
+/// level below the marginal one sells in full, the marginal level is shared pro rata
+/// and the rest sells nothing. `supply` is (reserve_price, quantity) per supply level.
+pub fn merit_order_fill(supply: &[(u64, u64)], clearing_price: u64, sold: u64) -> Result<MeritOrderFill> {
+    let mut eligible: Vec<(u64, u64)> = supply
+        .iter()
+        .copied()
+        .filter(|&(reserve_price, quantity)| quantity > 0 && reserve_met(reserve_price, clearing_price))
+        .collect();
+    eligible.sort_unstable_by_key(|&(reserve_price, _)| reserve_price);
+
+    let mut fill = MeritOrderFill::default();
+    for &(_, quantity) in &eligible {
+        fill.cleared_supply = fill.cleared_supply.checked_add(quantity).ok_or(EnergyAuctionError::MathError)?;
+    }
+    require!(sold <= fill.cleared_supply, EnergyAuctionError::MathError);
+
+    let mut remaining = sold;
+    for (reserve_price, quantity) in eligible {
+        if remaining <= quantity {
+            fill.marginal_reserve = reserve_price;
+            fill.marginal_supply = quantity;
+            fill.marginal_sold = remaining;
+            break;
+        }
+        remaining -= quantity;
+    }
+    Ok(fill)
+}
+
+/// Deserialize price-level aggregates of `timeslot` passed in remaining_accounts.
+/// Each account may appear once and must be owned by this program. Returns
+/// (price, quantity) per level and the quantity total.
+pub fn read_levels<T: AccountDeserialize>(
+    infos: &[AccountInfo],
+    timeslot: Pubkey,
+    fields: impl Fn(&T) -> (Pubkey, u64, u64),
+) -> Result<(Vec<(u64, u64)>, u64)> {
+    let mut seen: Vec<Pubkey> = Vec::with_capacity(infos.len());
+    let mut levels: Vec<(u64, u64)> = Vec::with_capacity(infos.len());
+    let mut total: u64 = 0;
+    for info in infos {
+        require!(!seen.contains(info.key), EnergyAuctionError::ConstraintViolation);
+        seen.push(*info.key);
+        require_keys_eq!(*info.owner, crate::ID, EnergyAuctionError::ConstraintViolation);
+        let level = T::try_deserialize(&mut &info.try_borrow_data()?[..])?;
+        let (level_timeslot, price, quantity) = fields(&level);
+        require_keys_eq!(level_timeslot, timeslot, EnergyAuctionError::ConstraintViolation);
+        total = total.checked_add(quantity).ok_or(EnergyAuctionError::MathError)?;
+        levels.push((price, quantity));
+    }
+    Ok((levels, total))
+}
+
+/// Proceeds owed to a seller with a met reserve: merit-order gross at the clearing price,
+/// the protocol fee on it, and the net paid out. Shared by withdraw_proceeds and preview_proceeds.
+pub fn seller_proceeds(ts: &Timeslot, supply: &Supply, fee_bps: u16) -> Result<ProceedsPreview> {
+    let allocated = ts.seller_allocation(supply.reserve_price, supply.amount)?;
+    let gross = (allocated as u128)
+        .checked_mul(ts.clearing_price as u128)
+        .ok_or(EnergyAuctionError::MathError)?;
+    let fee = gross
+        .checked_mul(fee_bps as u128)
+        .ok_or(EnergyAuctionError::MathError)?
+        .checked_div(10000)
+        .ok_or(EnergyAuctionError::MathError)?;
+    let net = gross.checked_sub(fee).ok_or(EnergyAuctionError::MathError)?;
+    Ok(ProceedsPreview {
+        allocated_quantity: allocated,
+        gross: u64::try_from(gross).map_err(|_| EnergyAuctionError::MathError)?,
+        fee: u64::try_from(fee).map_err(|_| EnergyAuctionError::MathError)?,
+        net: u64::try_from(net).map_err(|_| EnergyAuctionError::MathError)?,
+        claimed: supply.claimed,
+    })
+}
+
 ///////////////////////
 // State
 ///////////////////////
@@ -613,6 +1828,10 @@ pub struct GlobalState {
     pub version: u8,         // versioning for upgrades
     pub quote_mint: Pubkey,  // e.g., USDC
     pub fee_vault: Pubkey,   // PDA token account for protocol fees
+    pub shutdown: bool,      // permanent halt of new auctions/orders (irreversible)
+    pub current_round: u64,  // round number assigned to the next opened timeslot
+    pub pending_authority: Option<Pubkey>, // nominee awaiting accept_authority_transfer
+    pub min_auction_duration: i64, // seconds a timeslot must stay Open before sealing
 }
 
 impl GlobalState {
@@ -620,7 +1839,25 @@ impl GlobalState {
         + 2                    // fee_bps
         + 1                    // version
         + 32                   // quote_mint
-        + 32;                  // fee_vault
+        + 32                   // fee_vault
+        + 1                    // shutdown
+        + 8                    // current_round
+        + 1 + 32               // pending_authority (Option<Pubkey>)
+        + 8;                   // min_auction_duration
+}
+
+/// Flat protocol configuration returned by get_protocol_config
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct ProtocolConfig {
+    pub authority: Pubkey,
+    pub pending_authority: Option<Pubkey>,
+    pub fee_bps: u16,
+    pub version: u8,
+    pub quote_mint: Pubkey,
+    pub fee_vault: Pubkey,
+    pub shutdown: bool,
+    pub current_round: u64,
+    pub min_auction_duration: i64,
 }
 
 /// Minimal Supply struct for MVP (one-time immutable per timeslot)
@@ -629,31 +1866,88 @@ pub struct Supply {
     pub supplier: Pubkey,     // Who committed
     pub timeslot: Pubkey,     // timeslot account
     pub amount: u64,          // Amount committed (lots)
-    pub reserve_price: u64,   // min acceptable price per lot (quote units)
+    pub reserve_price: u64,   // min acceptable price per lot (quote units); 0 = price taker
     pub bump: u8,             // PDA bump
     pub energy_mint: Pubkey,  // energy token mint
     pub escrow_vault: Pubkey, // escrow token account for energy
     pub claimed: bool,        // Has the seller withdrawn proceeds?
+    pub delivered: u64,       // lots redeemed by buyers out of this escrow (capped at the allocation)
+    pub unsold_reclaimed: bool, // Has the seller taken back the lots that did not sell?
 }
 
 impl Supply {
-    pub const LEN: usize = 32 + 32 + 8 + 8 + 1 + 32 + 32 + 1;
+    pub const LEN: usize = 32 + 32 + 8 + 8 + 1 + 32 + 32 + 1 + 8 + 1;
+
+    /// Reserve of a price taker: sells at any clearing price and fills first in merit order
+    pub const PRICE_TAKER_RESERVE: u64 = 0;
+}
+
+/// Committed supply bucketed by reserve price (one per (timeslot, reserve_price)).
+/// Together these form the timeslot's supply curve without re-reading every Supply.
+#[account]
+pub struct SupplyLevelAggregate {
+    pub timeslot: Pubkey,     // which timeslot
+    pub reserve_price: u64,   // price bucket (quote units per lot)
+    pub total_quantity: u64,  // lots committed at this reserve
+    pub seller_count: u32,    // sellers contributing to this bucket
+}
+
+impl SupplyLevelAggregate {
+    pub const LEN: usize = 32  // timeslot
+        + 8                    // reserve_price
+        + 8                    // total_quantity
+        + 4;                   // seller_count
+}
+
+/// Active bid quantity bucketed by price (one per (timeslot, price)).
+/// Together these form the timeslot's demand curve without re-reading every BidPage.
+#[account]
+pub struct DemandLevelAggregate {
+    pub timeslot: Pubkey,     // which timeslot
+    pub price: u64,           // bid price bucket (quote units per lot)
+    pub total_quantity: u64,  // active lots bid at this price
+    pub bid_count: u32,       // active bids at this price
+}
+
+impl DemandLevelAggregate {
+    pub const LEN: usize = 32  // timeslot
+        + 8                    // price
+        + 8                    // total_quantity
+        + 4;                   // bid_count
 }
 
 /// Auction round container
 #[account]
 pub struct Timeslot {
-    pub epoch_ts: i64,        // identifies auction window
+    pub epoch_ts: i64,        // delivery-slot identifier (PDA seed); never used for timing
     pub status: u8,           // Pending=0, Open=1, Sealed=2, Settled=3, Cancelled=4
-    pub lot_size: u64,        // fixed per auction (1 kWh MVP)
+    pub lot_size: u64,        // fixed per auction (1 kWh MVP); immutable once supply/bids exist
     pub quote_mint: Pubkey,   // quote token (USDC)
     pub price_tick: u64,      // min price increment
     pub total_supply: u64,    // total committed lots
     pub total_bids: u64,      // total lots bid
-    pub head_page: Option<Pubkey>, // linked list of BidPages
-    pub tail_page: Option<Pubkey>, // last BidPage
+    pub head_page: Option<Pubkey>, // first BidPage created
+    pub tail_page: Option<Pubkey>, // most recently created BidPage
     pub clearing_price: u64,  // Final price determined after sealing
     pub total_sold_quantity: u64, // Final quantity sold in the auction
+    pub min_reserve_price: u64, // lowest reserve across committed supply (u64::MAX if none)
+    pub metadata_uri: [u8; 128], // zero-padded URI of off-chain metadata (region, node, certification)
+    pub min_bid_increment: u64, // required improvement over the best bid (0 = disabled)
+    // Running bid range over every bid ever placed. Cancellations do not tighten it
+    // (that would need a page scan), so treat both as outer bounds of the live book.
+    pub highest_bid_price: u64, // best bid price seen so far (0 if none)
+    pub lowest_bid_price: u64,  // worst bid price seen so far (u64::MAX if none)
+    pub max_total_supply: u64,  // grid capacity cap on committed lots (0 = unlimited)
+    pub max_total_bids: u64,    // demand-side cap on bid lots (0 = unlimited)
+    pub round: u64,             // sequential round number from GlobalState
+    pub min_reserve_bound: u64, // lowest reserve a seller may commit (0 = no floor)
+    pub max_reserve_bound: u64, // highest reserve a seller may commit (0 = no ceiling)
+    pub opened_at: i64,         // unix time the timeslot was opened; basis for all timing checks
+    pub cleared_supply: u64,    // lots whose reserve met the clearing price
+    pub energy_mint: Pubkey,    // energy token, fixed by the first commit (default until then)
+    pub marginal_reserve: u64,  // reserve of the supply level the sale ends in (merit order)
+    pub marginal_supply: u64,   // lots committed at the marginal reserve
+    pub marginal_sold: u64,     // lots sold at the marginal reserve, shared pro rata
 }
 
 impl Timeslot {
@@ -667,7 +1961,23 @@ impl Timeslot {
         + 1 + 32              // head_page (Option<Pubkey>)
         + 1 + 32              // tail_page (Option<Pubkey>)
         + 8                   // clearing_price
-        + 8;                  // total_sold_quantity
+        + 8                   // total_sold_quantity
+        + 8                   // min_reserve_price
+        + 128                 // metadata_uri
+        + 8                   // min_bid_increment
+        + 8                   // highest_bid_price
+        + 8                   // lowest_bid_price
+        + 8                   // max_total_supply
+        + 8                   // max_total_bids
+        + 8                   // round
+        + 8                   // min_reserve_bound
+        + 8                   // max_reserve_bound
+        + 8                   // opened_at
+        + 8                   // cleared_supply
+        + 32                  // energy_mint
+        + 8                   // marginal_reserve
+        + 8                   // marginal_supply
+        + 8;                  // marginal_sold
 
     pub fn status(&self) -> TimeslotStatus {
         match self.status {
@@ -678,8 +1988,116 @@ impl Timeslot {
             _ => TimeslotStatus::Cancelled,
         }
     }
+
+    /// Lots sold by a seller who committed `supply_amount` at `reserve_price`, in merit
+    /// order: all of it below the marginal reserve, a pro-rata share (rounded down) at it,
+    /// none above it.
+    pub fn seller_allocation(&self, reserve_price: u64, supply_amount: u64) -> Result<u64> {
+        if reserve_price < self.marginal_reserve {
+            return Ok(supply_amount);
+        }
+        if reserve_price > self.marginal_reserve || self.marginal_supply == 0 {
+            return Ok(0);
+        }
+        let allocated = (supply_amount as u128)
+            .checked_mul(self.marginal_sold as u128)
+            .ok_or(EnergyAuctionError::MathError)?
+            .checked_div(self.marginal_supply as u128)
+            .ok_or(EnergyAuctionError::MathError)?;
+        u64::try_from(allocated).map_err(|_| EnergyAuctionError::MathError.into())
+    }
+
+    /// Store the merit-order outcome of settlement
+    pub fn record_fill(&mut self, fill: &MeritOrderFill) {
+        self.cleared_supply = fill.cleared_supply;
+        self.marginal_reserve = fill.marginal_reserve;
+        self.marginal_supply = fill.marginal_supply;
+        self.marginal_sold = fill.marginal_sold;
+    }
+}
+
+/// Round-number index: lets clients derive a timeslot from its sequential round
+#[account]
+pub struct TimeslotRound {
+    pub timeslot: Pubkey, // timeslot opened in this round
+    pub epoch_ts: i64,    // its epoch_ts seed
+}
+
+impl TimeslotRound {
+    pub const LEN: usize = 32 + 8;
+}
+
+/// Optional physical limits for a timeslot, passed to open_timeslot (0 = unlimited)
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default)]
+pub struct TimeslotLimits {
+    pub max_total_supply: u64,
+    pub max_total_bids: u64,
+    pub min_reserve_price: u64,
+    pub max_reserve_price: u64,
+}
+
+/// Decoded view of a timeslot returned by get_auction_state
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct AuctionStateView {
+    pub status: TimeslotStatus,
+    pub epoch_ts: i64,
+    pub lot_size: u64,
+    pub price_tick: u64,
+    pub total_supply: u64,
+    pub total_bids: u64,
+    pub clearing_price: u64,
+    pub total_sold_quantity: u64,
+    pub min_reserve_price: u64,
+    pub highest_bid_price: u64,
+    pub lowest_bid_price: u64,
+    pub round: u64,
+    pub opened_at: i64,
+    pub min_bid_increment: u64,
+    pub max_total_supply: u64,
+    pub max_total_bids: u64,
+    pub min_reserve_bound: u64,
+    pub max_reserve_bound: u64,
+    pub cleared_supply: u64,
+    pub marginal_reserve: u64,
+    pub marginal_supply: u64,
+    pub marginal_sold: u64,
+}
+
+/// Seller payout breakdown returned by preview_proceeds
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct ProceedsPreview {
+    pub allocated_quantity: u64, // lots sold on this seller's behalf
+    pub gross: u64,              // allocated_quantity * clearing_price
+    pub fee: u64,                // protocol fee deducted from gross
+    pub net: u64,                // paid to the seller
+    pub claimed: bool,           // already withdrawn
+}
+
+/// Keeper decision returned by get_next_action
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct NextActionView {
+    pub action: NextAction,
+    pub blocker: ActionBlocker, // why the action would fail right now (None = go)
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub enum NextAction {
+    NeedsBids,    // Open with an empty order book
+    ReadyToSeal,  // Open with bids
+    ReadyToClear, // Sealed: execute_auction_clearing or settle_timeslot; cancel_timeslot if blocked
+    Complete,     // Settled: participants claim on their own
+    NeedsRefund,  // Cancelled: buyers cancel_bid, sellers withdraw_supply
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub enum ActionBlocker {
+    None,
+    AuctionTooShort,  // min_auction_duration has not elapsed
+    NoMarketClearing, // no bids to clear
+    NoIntersection,   // lowest reserve is above the highest bid
 }
 
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
 #[repr(u8)]
 pub enum TimeslotStatus {
     Pending = 0,
@@ -697,6 +2115,7 @@ pub struct Bid {
     pub quantity: u64,
     pub timestamp: i64,
     pub status: u8, // Active=0, Cancelled=1, Filled=2
+    pub nonce: u64, // client idempotency key (0 = none); unique per buyer within one page only
 }
 
 #[repr(u8)]
@@ -707,15 +2126,16 @@ impl Bid {
         + 8                    // price
         + 8                    // quantity
         + 8                    // timestamp
-        + 1;                   // status
+        + 1                    // status
+        + 8;                   // nonce
 }
 
-/// Page of bids (linked list)
+/// Page of bids, addressed by [b"bid_page", timeslot, page_index]
 #[account]
 pub struct BidPage {
     pub timeslot: Pubkey,         // which timeslot
     pub bids: Vec<Bid>,           // fixed max length (MVP: 150)
-    pub next_page: Option<Pubkey>,
+    pub next_page: Option<Pubkey>, // reserved, always None: scan pages by index
 }
 
 impl BidPage {
@@ -732,7 +2152,8 @@ pub struct FillReceipt {
     pub timeslot: Pubkey,
     pub quantity: u64,
     pub clearing_price: u64,
-    pub redeemed: bool,
+    pub redeemed: bool,       // every lot delivered
+    pub delivered: u64,       // lots delivered so far, possibly from several sellers
 }
 
 impl FillReceipt {
@@ -740,7 +2161,8 @@ impl FillReceipt {
         + 32                   // timeslot
         + 8                    // quantity
         + 8                    // clearing_price
-        + 1;                   // redeemed
+        + 1                    // redeemed
+        + 8;                   // delivered
 }
 
 /// Protocol fee vault (separate from sellers’ escrows)
@@ -762,7 +2184,7 @@ pub enum EnergyAuctionError {
     DuplicateSupply,
     #[msg("Timeslot is not in the correct state for this operation")]
     InvalidTimeslot,
-    #[msg("Insufficient token balance to commit supply")]
+    #[msg("Insufficient token balance for this transfer")]
     InsufficientBalance,
     #[msg("Math overflow/underflow error")]
     MathError,
@@ -776,4 +2198,40 @@ pub enum EnergyAuctionError {
     ConstraintViolation,
     #[msg("Proceeds or refund have already been claimed")]
     AlreadyClaimed,
+    #[msg("Price is not aligned to the timeslot price tick")]
+    PrecisionError,
+    #[msg("Lot size cannot change once supply or bids exist")]
+    LotSizeLocked,
+    #[msg("Escrow balance is insufficient for this payout")]
+    InsufficientEscrow,
+    #[msg("Bid does not beat the current highest bid by the minimum increment")]
+    BidIncrementTooSmall,
+    #[msg("Commit would exceed the timeslot's maximum total supply")]
+    SupplyCapExceeded,
+    #[msg("Bid would exceed the timeslot's maximum total bid quantity")]
+    DemandCapExceeded,
+    #[msg("Protocol has been permanently shut down")]
+    ProtocolShutdown,
+    #[msg("No bids were placed, so the market cannot clear")]
+    NoMarketClearing,
+    #[msg("Reserve price is outside the timeslot's allowed range")]
+    ReserveOutOfBounds,
+    #[msg("Seller's reserve price is above the clearing price")]
+    ReserveNotMet,
+    #[msg("Timeslot has not been open for the minimum auction duration")]
+    AuctionTooShort,
+    #[msg("A bid with this nonce already exists for the buyer on this page")]
+    DuplicateBid,
+    #[msg("Supply and demand curves do not intersect")]
+    NoIntersection,
+    #[msg("Price-level accounts do not cover the timeslot's full supply and demand")]
+    IncompleteOrderBook,
+    #[msg("Bid is not active")]
+    BidNotActive,
+    #[msg("Seller has no allocated energy left to deliver")]
+    AllocationExhausted,
+    #[msg("Seller has no unsold energy to reclaim")]
+    NothingToReclaim,
+    #[msg("Timeslot can still clear and cannot be cancelled")]
+    TimeslotClearable,
 }
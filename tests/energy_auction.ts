@@ -19,6 +19,7 @@ describe("energy_auction", () => {
 
   // test keypairs
   const seller = anchor.web3.Keypair.generate();
+  const secondSeller = anchor.web3.Keypair.generate();
   const buyer = anchor.web3.Keypair.generate();
   const poorBuyer = anchor.web3.Keypair.generate();
 
@@ -50,6 +51,31 @@ describe("energy_auction", () => {
     }, "confirmed");
   };
 
+  // helper: zero-pad a metadata URI into the fixed 128-byte on-chain field
+  const encodeMetadataUri = (uri: string): number[] => {
+    const buf = Buffer.alloc(128);
+    buf.write(uri, "utf8");
+    return Array.from(buf);
+  };
+
+  // helper: derive the round index PDA the next open_timeslot will create
+  const deriveNextRoundPda = async () => {
+    const state = await program.account.globalState.fetch(globalStatePda);
+    const [pda] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("timeslot_round"), state.currentRound.toArrayLike(Buffer, "le", 8)],
+      program.programId
+    );
+    return { pda, round: state.currentRound };
+  };
+
+  // open_timeslot limits with every cap disabled
+  const noLimits = {
+    maxTotalSupply: new anchor.BN(0),
+    maxTotalBids: new anchor.BN(0),
+    minReservePrice: new anchor.BN(0),
+    maxReservePrice: new anchor.BN(0),
+  };
+
   // helper: derive BidPage PDA from timeslot totalBids (reads on-chain)
   const deriveBidPagePda = async (timeslotPda: anchor.web3.PublicKey) => {
     const tsAcc = await program.account.timeslot.fetch(timeslotPda);
@@ -73,6 +99,12 @@ describe("energy_auction", () => {
     return { pda, pageIndex: pageIndexU32 };
   };
 
+  const deriveDemandLevelPda = (timeslotPda: anchor.web3.PublicKey, price: anchor.BN) =>
+    anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("demand_level"), timeslotPda.toBuffer(), price.toArrayLike(Buffer, "le", 8)],
+      program.programId
+    )[0];
+
 
   before(async () => {
     // fund seller/buyer/poorBuyer
@@ -198,6 +230,72 @@ describe("energy_auction", () => {
     assert.ok(state.feeVault.equals(feeVaultPda));
   });
 
+  it("✅ Reads the protocol config", async () => {
+    const config = await program.methods
+      .getProtocolConfig()
+      .accounts({ globalState: globalStatePda })
+      .view();
+
+    const state = await program.account.globalState.fetch(globalStatePda);
+    assert.ok(config.authority.equals(state.authority));
+    assert.equal(config.feeBps, state.feeBps);
+    assert.ok(config.quoteMint.equals(state.quoteMint));
+    assert.ok(config.feeVault.equals(feeVaultPda));
+    assert.isFalse(config.shutdown);
+    assert.isTrue(config.currentRound.eq(state.currentRound));
+  });
+
+  it("✅ Requires the nominee to accept an authority transfer", async () => {
+    const nominee = anchor.web3.Keypair.generate();
+
+    await program.methods
+      .proposeAuthorityTransfer(nominee.publicKey)
+      .accounts({
+        globalState: globalStatePda,
+        authority: authority.publicKey,
+      })
+      .rpc();
+
+    let state = await program.account.globalState.fetch(globalStatePda);
+    assert.ok(state.pendingAuthority.equals(nominee.publicKey));
+    assert.ok(state.authority.equals(authority.publicKey), "authority unchanged until accepted");
+
+    try {
+      await program.methods
+        .acceptAuthorityTransfer()
+        .accounts({
+          globalState: globalStatePda,
+          newAuthority: poorBuyer.publicKey,
+        })
+        .signers([poorBuyer])
+        .rpc();
+      assert.fail("Expected acceptance by a non-nominee to fail");
+    } catch (err) {
+      assert.instanceOf(err, AnchorError);
+      assert.equal((err as AnchorError).error.errorCode.code, "Unauthorized");
+    }
+
+    // re-nominate the current authority and accept, leaving the handoff state clean
+    await program.methods
+      .proposeAuthorityTransfer(authority.publicKey)
+      .accounts({
+        globalState: globalStatePda,
+        authority: authority.publicKey,
+      })
+      .rpc();
+    await program.methods
+      .acceptAuthorityTransfer()
+      .accounts({
+        globalState: globalStatePda,
+        newAuthority: authority.publicKey,
+      })
+      .rpc();
+
+    state = await program.account.globalState.fetch(globalStatePda);
+    assert.ok(state.authority.equals(authority.publicKey));
+    assert.isNull(state.pendingAuthority);
+  });
+
   it("✅ Opens a new timeslot", async () => {
     const [timeslotPda] = anchor.web3.PublicKey.findProgramAddressSync(
       [Buffer.from("timeslot"), epochTs.toArrayLike(Buffer, "le", 8)],
@@ -206,12 +304,16 @@ describe("energy_auction", () => {
 
     const lotSize = new anchor.BN(1); // 1 kWh per lot
     const priceTick = new anchor.BN(1_000_000); // $1.00 (6 dec)
+    const metadataUri = encodeMetadataUri("https://example.com/auctions/node-7.json");
+
+    const { pda: roundIndexPda, round } = await deriveNextRoundPda();
 
     await program.methods
-      .openTimeslot(epochTs, lotSize, priceTick)
+      .openTimeslot(epochTs, lotSize, priceTick, metadataUri, new anchor.BN(0), noLimits)
       .accounts({
         globalState: globalStatePda,
         timeslot: timeslotPda,
+        roundIndex: roundIndexPda,
         authority: authority.publicKey,
         systemProgram: anchor.web3.SystemProgram.programId,
       })
@@ -223,6 +325,37 @@ describe("energy_auction", () => {
     assert.isTrue(slot.lotSize.eq(lotSize));
     assert.isTrue(slot.priceTick.eq(priceTick));
     assert.isTrue(slot.totalSupply.eq(new anchor.BN(0)));
+    assert.deepEqual(Array.from(slot.metadataUri), metadataUri);
+    assert.isTrue(slot.round.eq(round));
+    assert.isTrue(slot.openedAt.gtn(0), "opened_at comes from the clock, not epoch_ts");
+
+    const roundIndex = await program.account.timeslotRound.fetch(roundIndexPda);
+    assert.ok(roundIndex.timeslot.equals(timeslotPda));
+    assert.isTrue(roundIndex.epochTs.eq(epochTs));
+
+    const state = await program.account.globalState.fetch(globalStatePda);
+    assert.isTrue(state.currentRound.eq(round.addn(1)));
+  });
+
+  it("✅ Updates timeslot metadata while open", async () => {
+    const [timeslotPda] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("timeslot"), epochTs.toArrayLike(Buffer, "le", 8)],
+      program.programId
+    );
+
+    const metadataUri = encodeMetadataUri("https://example.com/auctions/node-7-v2.json");
+
+    await program.methods
+      .updateTimeslotMetadata(metadataUri)
+      .accounts({
+        globalState: globalStatePda,
+        timeslot: timeslotPda,
+        authority: authority.publicKey,
+      })
+      .rpc();
+
+    const slot = await program.account.timeslot.fetch(timeslotPda);
+    assert.deepEqual(Array.from(slot.metadataUri), metadataUri);
   });
 
   it("✅ Allows a seller to commit supply", async () => {
@@ -244,6 +377,11 @@ describe("energy_auction", () => {
     const quantity = new anchor.BN(100);
     const reservePrice = new anchor.BN(10_000_000); // $10.00 (6 dec)
 
+    const [supplyLevelPda] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("supply_level"), timeslotPda.toBuffer(), reservePrice.toArrayLike(Buffer, "le", 8)],
+      program.programId
+    );
+
     const sellerBefore = (await getAccount(provider.connection, sellerEnergyAta)).amount;
 
     await program.methods
@@ -252,6 +390,7 @@ describe("energy_auction", () => {
         globalState: globalStatePda,
         timeslot: timeslotPda,
         supply: supplyPda,
+        supplyLevel: supplyLevelPda,
         energyMint: energyMint.publicKey,
         sellerSource: sellerEnergyAta,
         sellerEscrow: sellerEscrowPda,
@@ -275,6 +414,81 @@ describe("energy_auction", () => {
 
     const slot = await program.account.timeslot.fetch(timeslotPda);
     assert.isTrue(slot.totalSupply.eq(quantity));
+    assert.isTrue(slot.minReservePrice.eq(reservePrice));
+
+    const level = await program.account.supplyLevelAggregate.fetch(supplyLevelPda);
+    assert.ok(level.timeslot.equals(timeslotPda));
+    assert.isTrue(level.reservePrice.eq(reservePrice));
+    assert.isTrue(level.totalQuantity.eq(quantity));
+    assert.equal(level.sellerCount, 1);
+  });
+
+  it("✅ Aggregates supply from multiple sellers at the same reserve price", async () => {
+    await airdropAndConfirm(secondSeller.publicKey, 1 * anchor.web3.LAMPORTS_PER_SOL);
+
+    const secondSellerAta = await createAssociatedTokenAccount(
+      provider.connection,
+      secondSeller,
+      energyMint.publicKey,
+      secondSeller.publicKey
+    );
+    await mintTo(
+      provider.connection,
+      authority.payer,
+      energyMint.publicKey,
+      secondSellerAta,
+      authority.publicKey,
+      40
+    );
+
+    const [timeslotPda] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("timeslot"), epochTs.toArrayLike(Buffer, "le", 8)],
+      program.programId
+    );
+    const [firstSupplyPda] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("supply"), timeslotPda.toBuffer(), seller.publicKey.toBuffer()],
+      program.programId
+    );
+    const [supplyPda] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("supply"), timeslotPda.toBuffer(), secondSeller.publicKey.toBuffer()],
+      program.programId
+    );
+    const [sellerEscrowPda] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("seller_escrow"), timeslotPda.toBuffer(), secondSeller.publicKey.toBuffer()],
+      program.programId
+    );
+
+    const reservePrice = new anchor.BN(10_000_000); // same bucket as the first seller
+    const [supplyLevelPda] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("supply_level"), timeslotPda.toBuffer(), reservePrice.toArrayLike(Buffer, "le", 8)],
+      program.programId
+    );
+
+    await program.methods
+      .commitSupply(epochTs, reservePrice, new anchor.BN(25))
+      .accounts({
+        globalState: globalStatePda,
+        timeslot: timeslotPda,
+        supply: supplyPda,
+        supplyLevel: supplyLevelPda,
+        energyMint: energyMint.publicKey,
+        sellerSource: secondSellerAta,
+        sellerEscrow: sellerEscrowPda,
+        signer: secondSeller.publicKey,
+        systemProgram: anchor.web3.SystemProgram.programId,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([secondSeller])
+      .rpc();
+
+    const first = await program.account.supply.fetch(firstSupplyPda);
+    const second = await program.account.supply.fetch(supplyPda);
+    const level = await program.account.supplyLevelAggregate.fetch(supplyLevelPda);
+    assert.isTrue(level.totalQuantity.eq(first.amount.add(second.amount)));
+    assert.equal(level.sellerCount, 2);
+
+    const slot = await program.account.timeslot.fetch(timeslotPda);
+    assert.isTrue(slot.totalSupply.eq(level.totalQuantity));
   });
 
   it("✅ Allows a buyer to place a bid", async () => {
@@ -298,7 +512,7 @@ describe("energy_auction", () => {
     const buyerBefore = (await getAccount(provider.connection, buyerQuoteAta)).amount;
 
     await program.methods
-      .placeBid(pageIndex, price, quantity, timestamp)
+      .placeBid(pageIndex, price, quantity, timestamp, new anchor.BN(0))
       .accounts({
         globalState: globalStatePda,
         timeslot: timeslotPda,
@@ -307,6 +521,7 @@ describe("energy_auction", () => {
         buyerSource: buyerQuoteAta,
         buyer: buyer.publicKey,
         bidPage: bidPagePda,
+        demandLevel: deriveDemandLevelPda(timeslotPda, price),
         systemProgram: anchor.web3.SystemProgram.programId,
         tokenProgram: TOKEN_PROGRAM_ID,
       })
@@ -332,6 +547,10 @@ describe("energy_auction", () => {
 
     const slot = await program.account.timeslot.fetch(timeslotPda);
     assert.isTrue(slot.totalBids.eq(quantity));
+    assert.isTrue(slot.highestBidPrice.eq(price));
+    assert.isTrue(slot.lowestBidPrice.eq(price));
+    assert.ok(slot.headPage.equals(bidPagePda));
+    assert.ok(slot.tailPage.equals(bidPagePda));
   });
 
   it("🚫 Fails to place a bid with invalid price tick", async () => {
@@ -353,7 +572,7 @@ describe("energy_auction", () => {
 
     try {
       await program.methods
-        .placeBid(pageIndex, price, quantity, timestamp)
+        .placeBid(pageIndex, price, quantity, timestamp, new anchor.BN(0))
         .accounts({
           globalState: globalStatePda,
           timeslot: timeslotPda,
@@ -362,6 +581,7 @@ describe("energy_auction", () => {
           buyerSource: buyerQuoteAta,
           buyer: buyer.publicKey,
           bidPage: bidPagePda,
+          demandLevel: deriveDemandLevelPda(timeslotPda, price),
           systemProgram: anchor.web3.SystemProgram.programId,
           tokenProgram: TOKEN_PROGRAM_ID,
         })
@@ -374,6 +594,28 @@ describe("energy_auction", () => {
     }
   });
 
+  it("🚫 Fails to change lot size after activity has begun", async () => {
+    const [timeslotPda] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("timeslot"), epochTs.toArrayLike(Buffer, "le", 8)],
+      program.programId
+    );
+
+    try {
+      await program.methods
+        .setLotSize(new anchor.BN(10))
+        .accounts({
+          globalState: globalStatePda,
+          timeslot: timeslotPda,
+          authority: authority.publicKey,
+        })
+        .rpc();
+      assert.fail("Expected lot size change to fail once supply/bids exist");
+    } catch (err) {
+      assert.instanceOf(err, AnchorError);
+      assert.equal((err as AnchorError).error.errorCode.code, "LotSizeLocked");
+    }
+  });
+
   it("✅ Seals the timeslot", async () => {
     const [timeslotPda] = anchor.web3.PublicKey.findProgramAddressSync(
       [Buffer.from("timeslot"), epochTs.toArrayLike(Buffer, "le", 8)],
@@ -393,17 +635,175 @@ describe("energy_auction", () => {
     assert.equal(slot.status, 2); // Sealed
   });
 
+  it("✅ Reads the decoded auction state", async () => {
+    const [timeslotPda] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("timeslot"), epochTs.toArrayLike(Buffer, "le", 8)],
+      program.programId
+    );
+
+    const view = await program.methods
+      .getAuctionState()
+      .accounts({ timeslot: timeslotPda })
+      .view();
+
+    const slot = await program.account.timeslot.fetch(timeslotPda);
+    assert.deepEqual(view.status, { sealed: {} });
+    assert.isTrue(view.totalSupply.eq(slot.totalSupply));
+    assert.isTrue(view.totalBids.eq(slot.totalBids));
+    assert.isTrue(view.round.eq(slot.round));
+    assert.isTrue(view.openedAt.eq(slot.openedAt));
+    assert.isTrue(view.minBidIncrement.eq(slot.minBidIncrement));
+    assert.isTrue(view.maxTotalSupply.eq(slot.maxTotalSupply));
+    assert.isTrue(view.maxTotalBids.eq(slot.maxTotalBids));
+    assert.isTrue(view.minReserveBound.eq(slot.minReserveBound));
+    assert.isTrue(view.maxReserveBound.eq(slot.maxReserveBound));
+    assert.isTrue(view.clearedSupply.eq(slot.clearedSupply));
+    assert.isTrue(view.marginalReserve.eq(slot.marginalReserve));
+    assert.isTrue(view.marginalSupply.eq(slot.marginalSupply));
+    assert.isTrue(view.marginalSold.eq(slot.marginalSold));
+  });
+
+  it("✅ Reports the next keeper action for a sealed timeslot", async () => {
+    const [timeslotPda] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("timeslot"), epochTs.toArrayLike(Buffer, "le", 8)],
+      program.programId
+    );
+
+    const next = await program.methods
+      .getNextAction()
+      .accounts({ globalState: globalStatePda, timeslot: timeslotPda })
+      .view();
+
+    assert.deepEqual(next.action, { readyToClear: {} });
+    assert.deepEqual(next.blocker, { none: {} });
+  });
+
+  it("🚫 Fails to cancel a sealed timeslot that can still clear", async () => {
+    const [timeslotPda] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("timeslot"), epochTs.toArrayLike(Buffer, "le", 8)],
+      program.programId
+    );
+
+    try {
+      await program.methods
+        .cancelTimeslot()
+        .accounts({
+          globalState: globalStatePda,
+          timeslot: timeslotPda,
+          authority: authority.publicKey,
+        })
+        .rpc();
+      assert.fail("Expected cancelling a clearable timeslot to fail");
+    } catch (err) {
+      assert.instanceOf(err, AnchorError);
+      assert.equal((err as AnchorError).error.errorCode.code, "TimeslotClearable");
+    }
+  });
+
+  it("✅ Emits a snapshot carrying the full timeslot state", async () => {
+    const [timeslotPda] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("timeslot"), epochTs.toArrayLike(Buffer, "le", 8)],
+      program.programId
+    );
+
+    const sig = await program.methods
+      .emitTimeslotSnapshot()
+      .accounts({ timeslot: timeslotPda })
+      .rpc({ commitment: "confirmed" });
+    const tx = await provider.connection.getTransaction(sig, {
+      commitment: "confirmed",
+      maxSupportedTransactionVersion: 0,
+    });
+    const parser = new anchor.EventParser(program.programId, new anchor.BorshCoder(program.idl));
+    const events = [...parser.parseLogs(tx.meta.logMessages)];
+    assert.lengthOf(events, 1);
+    const snapshot = events[0].data;
+
+    const slot = await program.account.timeslot.fetch(timeslotPda);
+    assert.equal(snapshot.status, slot.status);
+    assert.isTrue(snapshot.totalSupply.eq(slot.totalSupply));
+    assert.isTrue(snapshot.totalBids.eq(slot.totalBids));
+    assert.isTrue(snapshot.minBidIncrement.eq(slot.minBidIncrement));
+    assert.isTrue(snapshot.maxTotalSupply.eq(slot.maxTotalSupply));
+    assert.isTrue(snapshot.maxTotalBids.eq(slot.maxTotalBids));
+    assert.isTrue(snapshot.minReserveBound.eq(slot.minReserveBound));
+    assert.isTrue(snapshot.maxReserveBound.eq(slot.maxReserveBound));
+    assert.isTrue(snapshot.clearedSupply.eq(slot.clearedSupply));
+    assert.isTrue(snapshot.marginalReserve.eq(slot.marginalReserve));
+    assert.isTrue(snapshot.marginalSupply.eq(slot.marginalSupply));
+    assert.isTrue(snapshot.marginalSold.eq(slot.marginalSold));
+    assert.isTrue(snapshot.quoteMint.equals(quoteMint.publicKey));
+    assert.isTrue(snapshot.energyMint.equals(energyMint.publicKey));
+    assert.deepEqual(Array.from(snapshot.metadataUri), Array.from(slot.metadataUri));
+  });
+
   // --- NEW SETTLEMENT TESTS ---
   describe("Settlement Flow", () => {
     const clearingPrice = new anchor.BN(11_000_000); // $11.00
     const totalSoldQuantity = new anchor.BN(50); // Buyer wins all 50 they bid for
 
+    it("🚫 Fails to settle with an off-grid clearing price", async () => {
+      const [timeslotPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("timeslot"), epochTs.toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+
+      // midpoint between the $10.00 reserve and the $12.00 bid, but $11.50 is off the $1.00 grid
+      const offGridPrice = new anchor.BN(11_500_000);
+
+      try {
+        await program.methods
+          .settleTimeslot(offGridPrice, totalSoldQuantity)
+          .accounts({
+            globalState: globalStatePda,
+            timeslot: timeslotPda,
+            authority: authority.publicKey,
+          })
+          .rpc();
+        assert.fail("Expected off-grid clearing price to fail");
+      } catch (err) {
+        assert.instanceOf(err, AnchorError);
+        assert.equal((err as AnchorError).error.errorCode.code, "PrecisionError");
+      }
+    });
+
+    it("🚫 Fails to settle more than the total bid quantity", async () => {
+      const [timeslotPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("timeslot"), epochTs.toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+
+      const slot = await program.account.timeslot.fetch(timeslotPda);
+      const overSold = slot.totalBids.addn(1); // still within total supply
+
+      try {
+        await program.methods
+          .settleTimeslot(clearingPrice, overSold)
+          .accounts({
+            globalState: globalStatePda,
+            timeslot: timeslotPda,
+            authority: authority.publicKey,
+          })
+          .rpc();
+        assert.fail("Expected settling above total demand to fail");
+      } catch (err) {
+        assert.instanceOf(err, AnchorError);
+        assert.equal((err as AnchorError).error.errorCode.code, "MathError");
+      }
+    });
+
     it("✅ Settles the timeslot", async () => {
       const [timeslotPda] = anchor.web3.PublicKey.findProgramAddressSync(
         [Buffer.from("timeslot"), epochTs.toArrayLike(Buffer, "le", 8)],
         program.programId
       );
       
+      // both sellers committed at the same reserve, so the timeslot has one supply level
+      const [supplyLevelPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("supply_level"), timeslotPda.toBuffer(), new anchor.BN(10_000_000).toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+
       await program.methods
         .settleTimeslot(clearingPrice, totalSoldQuantity)
         .accounts({
@@ -411,6 +811,7 @@ describe("energy_auction", () => {
           timeslot: timeslotPda,
           authority: authority.publicKey,
         })
+        .remainingAccounts([{ pubkey: supplyLevelPda, isSigner: false, isWritable: false }])
         .rpc();
 
       const slot = await program.account.timeslot.fetch(timeslotPda);
@@ -451,6 +852,42 @@ describe("energy_auction", () => {
       assert.isFalse(receipt.redeemed);
     });
 
+    it("🚫 Fails to pay proceeds into a quote account the seller does not own", async () => {
+      const [timeslotPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("timeslot"), epochTs.toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      const [supplyPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("supply"), timeslotPda.toBuffer(), seller.publicKey.toBuffer()],
+        program.programId
+      );
+      const [timeslotQuoteEscrow] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("quote_escrow"), timeslotPda.toBuffer()],
+        program.programId
+      );
+
+      try {
+        await program.methods
+          .withdrawProceeds()
+          .accounts({
+            globalState: globalStatePda,
+            timeslot: timeslotPda,
+            supply: supplyPda,
+            timeslotQuoteEscrow: timeslotQuoteEscrow,
+            feeVault: feeVaultPda,
+            sellerProceedsAta: buyerQuoteAta, // right mint, wrong owner
+            seller: seller.publicKey,
+            tokenProgram: TOKEN_PROGRAM_ID,
+          })
+          .signers([seller])
+          .rpc();
+        assert.fail("Expected proceeds to a foreign account to fail");
+      } catch (err) {
+        assert.instanceOf(err, AnchorError);
+        assert.equal((err as AnchorError).error.errorCode.code, "Unauthorized");
+      }
+    });
+
     it("✅ Allows seller to withdraw proceeds", async () => {
       const [timeslotPda] = anchor.web3.PublicKey.findProgramAddressSync(
         [Buffer.from("timeslot"), epochTs.toArrayLike(Buffer, "le", 8)],
@@ -466,10 +903,25 @@ describe("energy_auction", () => {
       );
 
       const globalState = await program.account.globalState.fetch(globalStatePda);
-      const grossProceeds = totalSoldQuantity.mul(clearingPrice);
+      // both sellers sit at the marginal reserve, so this seller is paid their pro-rata share
+      const slot = await program.account.timeslot.fetch(timeslotPda);
+      const supplyAcc = await program.account.supply.fetch(supplyPda);
+      assert.isTrue(slot.marginalReserve.eq(supplyAcc.reservePrice));
+      const allocated = supplyAcc.amount.mul(slot.marginalSold).div(slot.marginalSupply);
+      const grossProceeds = allocated.mul(clearingPrice);
       const fee = grossProceeds.mul(new anchor.BN(globalState.feeBps)).div(new anchor.BN(10000));
       const expectedNetProceeds = grossProceeds.sub(fee);
 
+      const preview = await program.methods
+        .previewProceeds()
+        .accounts({ globalState: globalStatePda, timeslot: timeslotPda, supply: supplyPda })
+        .view();
+      assert.isTrue(preview.allocatedQuantity.eq(allocated));
+      assert.isTrue(preview.gross.eq(grossProceeds));
+      assert.isTrue(preview.fee.eq(fee));
+      assert.isTrue(preview.net.eq(expectedNetProceeds));
+      assert.isFalse(preview.claimed);
+
       const sellerQuoteBefore = (await getAccount(provider.connection, sellerQuoteAta)).amount;
       const feeVaultBefore = (await getAccount(provider.connection, feeVaultPda)).amount;
 
@@ -506,7 +958,7 @@ describe("energy_auction", () => {
       assert.isTrue(updatedSupply.claimed, "Supply should be marked as claimed");
     });
 
-    it("✅ Allows buyer to redeem energy and get a refund", async () => {
+    it("🚫 Fails to redeem against an account that is not the global state", async () => {
       const [timeslotPda] = anchor.web3.PublicKey.findProgramAddressSync(
         [Buffer.from("timeslot"), epochTs.toArrayLike(Buffer, "le", 8)],
         program.programId
@@ -519,11 +971,105 @@ describe("energy_auction", () => {
         [Buffer.from("quote_escrow"), timeslotPda.toBuffer()],
         program.programId
       );
+      const [supplyPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("supply"), timeslotPda.toBuffer(), seller.publicKey.toBuffer()],
+        program.programId
+      );
       const [sellerEscrowPda] = anchor.web3.PublicKey.findProgramAddressSync(
         [Buffer.from("seller_escrow"), timeslotPda.toBuffer(), seller.publicKey.toBuffer()],
         program.programId
       );
 
+      try {
+        await program.methods
+          .redeemEnergyAndRefund(new anchor.BN(12_000_000).mul(new anchor.BN(50)))
+          .accounts({
+            globalState: timeslotPda, // program-owned, but not the global state PDA
+            timeslot: timeslotPda,
+            fillReceipt: fillReceiptPda,
+            timeslotQuoteEscrow: timeslotQuoteEscrow,
+            buyerQuoteAta: buyerQuoteAta,
+            buyerEnergyAta: buyerEnergyAta,
+            supply: supplyPda,
+            sellerEscrow: sellerEscrowPda,
+            buyer: buyer.publicKey,
+            tokenProgram: TOKEN_PROGRAM_ID,
+          })
+          .signers([buyer])
+          .rpc();
+        assert.fail("Expected redemption with a substitute global state to fail");
+      } catch (err) {
+        assert.instanceOf(err, AnchorError);
+        assert.equal((err as AnchorError).error.errorCode.code, "AccountDiscriminatorMismatch");
+      }
+    });
+
+    it("🚫 Fails to redeem from an energy account that is not the seller's escrow", async () => {
+      const [timeslotPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("timeslot"), epochTs.toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      const [fillReceiptPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("fill_receipt"), timeslotPda.toBuffer(), buyer.publicKey.toBuffer()],
+        program.programId
+      );
+      const [timeslotQuoteEscrow] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("quote_escrow"), timeslotPda.toBuffer()],
+        program.programId
+      );
+
+      const [supplyPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("supply"), timeslotPda.toBuffer(), seller.publicKey.toBuffer()],
+        program.programId
+      );
+      try {
+        await program.methods
+          .redeemEnergyAndRefund(new anchor.BN(12_000_000).mul(new anchor.BN(50)))
+          .accounts({
+            globalState: globalStatePda,
+            timeslot: timeslotPda,
+            fillReceipt: fillReceiptPda,
+            timeslotQuoteEscrow: timeslotQuoteEscrow,
+            buyerQuoteAta: buyerQuoteAta,
+            buyerEnergyAta: buyerEnergyAta,
+            supply: supplyPda,
+            sellerEscrow: sellerEnergyAta, // right mint, but not the escrow recorded on the supply
+            buyer: buyer.publicKey,
+            tokenProgram: TOKEN_PROGRAM_ID,
+          })
+          .signers([buyer])
+          .rpc();
+        assert.fail("Expected redemption from a non-escrow account to fail");
+      } catch (err) {
+        assert.instanceOf(err, AnchorError);
+        assert.equal((err as AnchorError).error.errorCode.code, "InvalidEscrowVault");
+      }
+    });
+
+    it("✅ Allows buyer to redeem energy and get a refund", async () => {
+      const [timeslotPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("timeslot"), epochTs.toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      const [fillReceiptPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("fill_receipt"), timeslotPda.toBuffer(), buyer.publicKey.toBuffer()],
+        program.programId
+      );
+      const [timeslotQuoteEscrow] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("quote_escrow"), timeslotPda.toBuffer()],
+        program.programId
+      );
+      const sellerPdas = (kp: anchor.web3.Keypair) => ({
+        supply: anchor.web3.PublicKey.findProgramAddressSync(
+          [Buffer.from("supply"), timeslotPda.toBuffer(), kp.publicKey.toBuffer()],
+          program.programId
+        )[0],
+        sellerEscrow: anchor.web3.PublicKey.findProgramAddressSync(
+          [Buffer.from("seller_escrow"), timeslotPda.toBuffer(), kp.publicKey.toBuffer()],
+          program.programId
+        )[0],
+      });
+
       // Calculate expected refund
       const bidPrice = new anchor.BN(12_000_000);
       const bidQuantity = new anchor.BN(50);
@@ -533,40 +1079,163 @@ describe("energy_auction", () => {
       const actualCost = receipt.quantity.mul(receipt.clearingPrice);
       const expectedRefund = totalBidAmountEscrowed.sub(actualCost);
 
+      const redeemFrom = (kp: anchor.web3.Keypair) =>
+        program.methods
+          .redeemEnergyAndRefund(totalBidAmountEscrowed)
+          .accounts({
+            globalState: globalStatePda,
+            timeslot: timeslotPda,
+            fillReceipt: fillReceiptPda,
+            timeslotQuoteEscrow: timeslotQuoteEscrow,
+            buyerQuoteAta: buyerQuoteAta,
+            buyerEnergyAta: buyerEnergyAta,
+            ...sellerPdas(kp),
+            buyer: buyer.publicKey,
+            tokenProgram: TOKEN_PROGRAM_ID,
+          })
+          .signers([buyer])
+          .rpc();
+
       const buyerQuoteBefore = (await getAccount(provider.connection, buyerQuoteAta)).amount;
       const buyerEnergyBefore = (await getAccount(provider.connection, buyerEnergyAta)).amount;
 
-      await program.methods
-        .redeemEnergyAndRefund(totalBidAmountEscrowed)
-        .accounts({
-          timeslot: timeslotPda,
-          fillReceipt: fillReceiptPda,
-          timeslotQuoteEscrow: timeslotQuoteEscrow,
-          buyerQuoteAta: buyerQuoteAta,
-          buyerEnergyAta: buyerEnergyAta,
-          sellerEscrow: sellerEscrowPda, // NOTE: Assuming one seller for simplicity
-          buyer: buyer.publicKey,
-          tokenProgram: TOKEN_PROGRAM_ID,
-        })
-        .signers([buyer])
-        .rpc();
+      // 50 of 125 lots sold at one reserve: the first seller delivers 40 of their 100
+      await redeemFrom(seller);
 
       const buyerQuoteAfter = (await getAccount(provider.connection, buyerQuoteAta)).amount;
-      const buyerEnergyAfter = (await getAccount(provider.connection, buyerEnergyAta)).amount;
+      let buyerEnergyAfter = (await getAccount(provider.connection, buyerEnergyAta)).amount;
 
       assert.equal(
         (buyerQuoteAfter - buyerQuoteBefore).toString(),
         expectedRefund.toString(),
         "Buyer should receive a refund"
       );
+      assert.equal((buyerEnergyAfter - buyerEnergyBefore).toString(), "40");
+      let updatedReceipt = await program.account.fillReceipt.fetch(fillReceiptPda);
+      assert.equal(updatedReceipt.delivered.toNumber(), 40);
+      assert.isFalse(updatedReceipt.redeemed);
+
+      // the first seller's allocation is used up
+      try {
+        await redeemFrom(seller);
+        assert.fail("Expected redemption beyond the seller's allocation to fail");
+      } catch (err) {
+        assert.instanceOf(err, AnchorError);
+        assert.equal((err as AnchorError).error.errorCode.code, "AllocationExhausted");
+      }
+
+      // the second seller delivers the remaining 10 of their 25; the refund is not paid twice
+      await redeemFrom(secondSeller);
+
+      const buyerQuoteFinal = (await getAccount(provider.connection, buyerQuoteAta)).amount;
+      buyerEnergyAfter = (await getAccount(provider.connection, buyerEnergyAta)).amount;
+      assert.equal(buyerQuoteFinal.toString(), buyerQuoteAfter.toString());
       assert.equal(
         (buyerEnergyAfter - buyerEnergyBefore).toString(),
         receipt.quantity.toString(),
         "Buyer should receive energy tokens"
       );
 
-      const updatedReceipt = await program.account.fillReceipt.fetch(fillReceiptPda);
+      updatedReceipt = await program.account.fillReceipt.fetch(fillReceiptPda);
       assert.isTrue(updatedReceipt.redeemed, "Receipt should be marked as redeemed");
+      const secondSupply = await program.account.supply.fetch(sellerPdas(secondSeller).supply);
+      assert.equal(secondSupply.delivered.toNumber(), 10);
+    });
+
+    it("🚫 Fails to redeem the same fill receipt twice", async () => {
+      const [timeslotPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("timeslot"), epochTs.toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      const [fillReceiptPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("fill_receipt"), timeslotPda.toBuffer(), buyer.publicKey.toBuffer()],
+        program.programId
+      );
+      const [timeslotQuoteEscrow] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("quote_escrow"), timeslotPda.toBuffer()],
+        program.programId
+      );
+      const [supplyPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("supply"), timeslotPda.toBuffer(), seller.publicKey.toBuffer()],
+        program.programId
+      );
+      const [sellerEscrowPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("seller_escrow"), timeslotPda.toBuffer(), seller.publicKey.toBuffer()],
+        program.programId
+      );
+      const buyerEnergyBefore = (await getAccount(provider.connection, buyerEnergyAta)).amount;
+
+      try {
+        await program.methods
+          .redeemEnergyAndRefund(new anchor.BN(12_000_000).mul(new anchor.BN(50)))
+          .accounts({
+            globalState: globalStatePda,
+            timeslot: timeslotPda,
+            fillReceipt: fillReceiptPda,
+            timeslotQuoteEscrow: timeslotQuoteEscrow,
+            buyerQuoteAta: buyerQuoteAta,
+            buyerEnergyAta: buyerEnergyAta,
+            supply: supplyPda,
+            sellerEscrow: sellerEscrowPda,
+            buyer: buyer.publicKey,
+            tokenProgram: TOKEN_PROGRAM_ID,
+          })
+          .signers([buyer])
+          .rpc();
+        assert.fail("Expected a second redemption to fail");
+      } catch (err) {
+        assert.instanceOf(err, AnchorError);
+        assert.equal((err as AnchorError).error.errorCode.code, "AlreadyClaimed");
+      }
+
+      const buyerEnergyAfter = (await getAccount(provider.connection, buyerEnergyAta)).amount;
+      assert.equal(buyerEnergyAfter.toString(), buyerEnergyBefore.toString());
+    });
+
+    it("✅ Lets a seller reclaim the energy that did not sell", async () => {
+      const [timeslotPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("timeslot"), epochTs.toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      const [supplyPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("supply"), timeslotPda.toBuffer(), seller.publicKey.toBuffer()],
+        program.programId
+      );
+      const [sellerEscrowPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("seller_escrow"), timeslotPda.toBuffer(), seller.publicKey.toBuffer()],
+        program.programId
+      );
+      const reclaim = () =>
+        program.methods
+          .reclaimUnsoldEnergy()
+          .accounts({
+            timeslot: timeslotPda,
+            supply: supplyPda,
+            sellerEscrow: sellerEscrowPda,
+            sellerDestination: sellerEnergyAta,
+            seller: seller.publicKey,
+            tokenProgram: TOKEN_PROGRAM_ID,
+          })
+          .signers([seller])
+          .rpc();
+
+      const before = (await getAccount(provider.connection, sellerEnergyAta)).amount;
+      await reclaim();
+      const after = (await getAccount(provider.connection, sellerEnergyAta)).amount;
+
+      // 100 committed, 40 allocated to the buyer
+      assert.equal((after - before).toString(), "60");
+      assert.equal((await getAccount(provider.connection, sellerEscrowPda)).amount.toString(), "0");
+      const supplyAcc = await program.account.supply.fetch(supplyPda);
+      assert.isTrue(supplyAcc.unsoldReclaimed);
+
+      try {
+        await reclaim();
+        assert.fail("Expected a second reclaim to fail");
+      } catch (err) {
+        assert.instanceOf(err, AnchorError);
+        assert.equal((err as AnchorError).error.errorCode.code, "AlreadyClaimed");
+      }
     });
   });
 
@@ -591,7 +1260,7 @@ describe("energy_auction", () => {
   
       try {
         await program.methods
-          .placeBid(pageIndex, price, quantity, timestamp)
+          .placeBid(pageIndex, price, quantity, timestamp, new anchor.BN(0))
           .accounts({
             globalState: globalStatePda,
             timeslot: timeslotPda,
@@ -600,6 +1269,7 @@ describe("energy_auction", () => {
             buyerSource: buyerQuoteAta,
             buyer: buyer.publicKey,
             bidPage: bidPagePda,
+            demandLevel: deriveDemandLevelPda(timeslotPda, price),
             systemProgram: anchor.web3.SystemProgram.programId,
             tokenProgram: TOKEN_PROGRAM_ID,
           })
@@ -612,6 +1282,52 @@ describe("energy_auction", () => {
       }
     });
   
+    it("🚫 Fails to initialize a new bid page on a sealed timeslot", async () => {
+      const [timeslotPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("timeslot"), epochTs.toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      const [timeslotQuoteEscrow] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("quote_escrow"), timeslotPda.toBuffer()],
+        program.programId
+      );
+
+      // a page index that was never used while the timeslot was open
+      const freshPageIndex = 7;
+      const pageIndexBuffer = Buffer.alloc(4);
+      pageIndexBuffer.writeUInt32LE(freshPageIndex, 0);
+      const [freshPagePda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("bid_page"), timeslotPda.toBuffer(), pageIndexBuffer],
+        program.programId
+      );
+
+      try {
+        await program.methods
+          .placeBid(freshPageIndex, new anchor.BN(13_000_000), new anchor.BN(1), new anchor.BN(Date.now()), new anchor.BN(0))
+          .accounts({
+            globalState: globalStatePda,
+            timeslot: timeslotPda,
+            timeslotQuoteEscrow,
+            quoteMint: quoteMint.publicKey,
+            buyerSource: buyerQuoteAta,
+            buyer: buyer.publicKey,
+            bidPage: freshPagePda,
+            demandLevel: deriveDemandLevelPda(timeslotPda, new anchor.BN(13_000_000)),
+            systemProgram: anchor.web3.SystemProgram.programId,
+            tokenProgram: TOKEN_PROGRAM_ID,
+          })
+          .signers([buyer])
+          .rpc();
+        assert.fail("Expected page init on a sealed timeslot to fail");
+      } catch (err) {
+        assert.instanceOf(err, AnchorError);
+        assert.equal((err as AnchorError).error.errorCode.code, "InvalidTimeslot");
+      }
+
+      const pageInfo = await provider.connection.getAccountInfo(freshPagePda);
+      assert.isNull(pageInfo, "No bid page should exist after the failed init");
+    });
+
     it("🚫 Fails to commit supply to a sealed timeslot", async () => {
       const anotherSeller = anchor.web3.Keypair.generate();
       await airdropAndConfirm(anotherSeller.publicKey, 1 * anchor.web3.LAMPORTS_PER_SOL);
@@ -647,13 +1363,20 @@ describe("energy_auction", () => {
         program.programId
       );
   
+      const reservePrice = new anchor.BN(1_000_000);
+      const [supplyLevelPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("supply_level"), timeslotPda.toBuffer(), reservePrice.toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+  
       try {
         await program.methods
-          .commitSupply(epochTs, new anchor.BN(1_000_000), new anchor.BN(1))
+          .commitSupply(epochTs, reservePrice, new anchor.BN(1))
           .accounts({
             globalState: globalStatePda,
             timeslot: timeslotPda,
             supply: supplyPda,
+            supplyLevel: supplyLevelPda,
             energyMint: energyMint.publicKey,
             sellerSource: anotherSellerAta,
             sellerEscrow: sellerEscrowPda,
@@ -670,64 +1393,1873 @@ describe("energy_auction", () => {
       }
     });
   
-    it("🚫 Fails to place a bid with insufficient buyer balance", async () => {
-      // create new timeslot for this negative test
-      const newEpoch = new anchor.BN(Date.now() + 10_000);
-      const [newTimeslotPda] = anchor.web3.PublicKey.findProgramAddressSync(
-        [Buffer.from("timeslot"), newEpoch.toArrayLike(Buffer, "le", 8)],
+    it("🚫 Fails to commit supply beyond the timeslot's maximum total supply", async () => {
+      const capEpoch = new anchor.BN(Date.now() + 30_000);
+      const [capTimeslotPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("timeslot"), capEpoch.toArrayLike(Buffer, "le", 8)],
         program.programId
       );
-  
+
+      const { pda: roundIndexPda } = await deriveNextRoundPda();
+
       await program.methods
-        .openTimeslot(newEpoch, new anchor.BN(1), new anchor.BN(1_000_000))
+        .openTimeslot(capEpoch, new anchor.BN(1), new anchor.BN(1_000_000), encodeMetadataUri(""), new anchor.BN(0), {
+          ...noLimits,
+          maxTotalSupply: new anchor.BN(10),
+        })
         .accounts({
           globalState: globalStatePda,
-          timeslot: newTimeslotPda,
+          timeslot: capTimeslotPda,
+          roundIndex: roundIndexPda,
           authority: authority.publicKey,
           systemProgram: anchor.web3.SystemProgram.programId,
         })
         .rpc();
-  
-      const [newTimeslotEscrow] = anchor.web3.PublicKey.findProgramAddressSync(
-        [Buffer.from("quote_escrow"), newTimeslotPda.toBuffer()],
+
+      const [supplyPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("supply"), capTimeslotPda.toBuffer(), seller.publicKey.toBuffer()],
         program.programId
       );
-  
-      const { pda: bidPagePda, pageIndex } = await deriveBidPagePda(newTimeslotPda);
-  
-      const price = new anchor.BN(1_000_000); // $1.00
-      const quantity = new anchor.BN(10_000); // requires 10,000 USDC, poorBuyer has 0.001
-      const timestamp = new anchor.BN(Date.now());
-  
+      const [sellerEscrowPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("seller_escrow"), capTimeslotPda.toBuffer(), seller.publicKey.toBuffer()],
+        program.programId
+      );
+      const reservePrice = new anchor.BN(1_000_000);
+      const [supplyLevelPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("supply_level"), capTimeslotPda.toBuffer(), reservePrice.toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+
       try {
         await program.methods
-          .placeBid(pageIndex, price, quantity, timestamp)
+          .commitSupply(capEpoch, reservePrice, new anchor.BN(20))
           .accounts({
             globalState: globalStatePda,
-            timeslot: newTimeslotPda,
-            timeslotQuoteEscrow: newTimeslotEscrow,
-            quoteMint: quoteMint.publicKey,
-            buyerSource: poorBuyerQuoteAta,
-            buyer: poorBuyer.publicKey,
-            bidPage: bidPagePda,
+            timeslot: capTimeslotPda,
+            supply: supplyPda,
+            supplyLevel: supplyLevelPda,
+            energyMint: energyMint.publicKey,
+            sellerSource: sellerEnergyAta,
+            sellerEscrow: sellerEscrowPda,
+            signer: seller.publicKey,
             systemProgram: anchor.web3.SystemProgram.programId,
             tokenProgram: TOKEN_PROGRAM_ID,
           })
-          .signers([poorBuyer])
+          .signers([seller])
           .rpc();
-        assert.fail("Expected bid to fail due to insufficient funds");
+        assert.fail("Expected commit above the supply cap to fail");
       } catch (err) {
-        // The error from an insufficient funds transfer via CPI can be generic.
-        // We check for common RPC error messages or the specific token program error.
-        const errorString = err.toString();
-        const isExpectedError =
-          errorString.includes("failed to send transaction") ||
-          errorString.includes("Simulation failed") ||
-          errorString.includes("insufficient funds") ||
-          errorString.includes("custom program error: 0x1"); // SPL Token program's InsufficientFunds error
-  
-        assert.isTrue(isExpectedError, `Unexpected error received: ${errorString}`);
+        assert.instanceOf(err, AnchorError);
+        assert.equal((err as AnchorError).error.errorCode.code, "SupplyCapExceeded");
       }
     });
+
+    it("🚫 Fails to place bids beyond the timeslot's maximum total bids", async () => {
+      const capEpoch = new anchor.BN(Date.now() + 110_000);
+      const [capTimeslotPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("timeslot"), capEpoch.toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      const [capQuoteEscrow] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("quote_escrow"), capTimeslotPda.toBuffer()],
+        program.programId
+      );
+
+      const { pda: roundIndexPda } = await deriveNextRoundPda();
+
+      await program.methods
+        .openTimeslot(capEpoch, new anchor.BN(1), new anchor.BN(1_000_000), encodeMetadataUri(""), new anchor.BN(0), {
+          ...noLimits,
+          maxTotalBids: new anchor.BN(10),
+        })
+        .accounts({
+          globalState: globalStatePda,
+          timeslot: capTimeslotPda,
+          roundIndex: roundIndexPda,
+          authority: authority.publicKey,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .rpc();
+
+      const price = new anchor.BN(1_000_000);
+      const { pda: bidPagePda, pageIndex } = await deriveBidPagePda(capTimeslotPda);
+      try {
+        await program.methods
+          .placeBid(pageIndex, price, new anchor.BN(20), new anchor.BN(Date.now()), new anchor.BN(0))
+          .accounts({
+            globalState: globalStatePda,
+            timeslot: capTimeslotPda,
+            timeslotQuoteEscrow: capQuoteEscrow,
+            quoteMint: quoteMint.publicKey,
+            buyerSource: buyerQuoteAta,
+            buyer: buyer.publicKey,
+            bidPage: bidPagePda,
+            demandLevel: deriveDemandLevelPda(capTimeslotPda, price),
+            systemProgram: anchor.web3.SystemProgram.programId,
+            tokenProgram: TOKEN_PROGRAM_ID,
+          })
+          .signers([buyer])
+          .rpc();
+        assert.fail("Expected bid above the demand cap to fail");
+      } catch (err) {
+        assert.instanceOf(err, AnchorError);
+        assert.equal((err as AnchorError).error.errorCode.code, "DemandCapExceeded");
+      }
+    });
+
+    it("🚫 Fails to commit supply with a reserve outside the timeslot bounds", async () => {
+      const boundedEpoch = new anchor.BN(Date.now() + 50_000);
+      const [boundedTimeslotPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("timeslot"), boundedEpoch.toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+
+      const { pda: roundIndexPda } = await deriveNextRoundPda();
+
+      await program.methods
+        .openTimeslot(boundedEpoch, new anchor.BN(1), new anchor.BN(1_000_000), encodeMetadataUri(""), new anchor.BN(0), {
+          ...noLimits,
+          minReservePrice: new anchor.BN(2_000_000),
+          maxReservePrice: new anchor.BN(5_000_000),
+        })
+        .accounts({
+          globalState: globalStatePda,
+          timeslot: boundedTimeslotPda,
+          roundIndex: roundIndexPda,
+          authority: authority.publicKey,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .rpc();
+
+      const [supplyPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("supply"), boundedTimeslotPda.toBuffer(), seller.publicKey.toBuffer()],
+        program.programId
+      );
+      const [sellerEscrowPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("seller_escrow"), boundedTimeslotPda.toBuffer(), seller.publicKey.toBuffer()],
+        program.programId
+      );
+      const reservePrice = new anchor.BN(6_000_000); // above the $5.00 ceiling
+      const [supplyLevelPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("supply_level"), boundedTimeslotPda.toBuffer(), reservePrice.toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+
+      try {
+        await program.methods
+          .commitSupply(boundedEpoch, reservePrice, new anchor.BN(1))
+          .accounts({
+            globalState: globalStatePda,
+            timeslot: boundedTimeslotPda,
+            supply: supplyPda,
+            supplyLevel: supplyLevelPda,
+            energyMint: energyMint.publicKey,
+            sellerSource: sellerEnergyAta,
+            sellerEscrow: sellerEscrowPda,
+            signer: seller.publicKey,
+            systemProgram: anchor.web3.SystemProgram.programId,
+            tokenProgram: TOKEN_PROGRAM_ID,
+          })
+          .signers([seller])
+          .rpc();
+        assert.fail("Expected reserve above the ceiling to fail");
+      } catch (err) {
+        assert.instanceOf(err, AnchorError);
+        assert.equal((err as AnchorError).error.errorCode.code, "ReserveOutOfBounds");
+      }
+    });
+
+    it("✅ Accepts a price-taker reserve of zero despite a reserve floor", async () => {
+      const takerEpoch = new anchor.BN(Date.now() + 100_000);
+      const [takerTimeslotPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("timeslot"), takerEpoch.toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+
+      const { pda: roundIndexPda } = await deriveNextRoundPda();
+
+      await program.methods
+        .openTimeslot(takerEpoch, new anchor.BN(1), new anchor.BN(1_000_000), encodeMetadataUri(""), new anchor.BN(0), {
+          ...noLimits,
+          minReservePrice: new anchor.BN(2_000_000),
+        })
+        .accounts({
+          globalState: globalStatePda,
+          timeslot: takerTimeslotPda,
+          roundIndex: roundIndexPda,
+          authority: authority.publicKey,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .rpc();
+
+      const [supplyPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("supply"), takerTimeslotPda.toBuffer(), seller.publicKey.toBuffer()],
+        program.programId
+      );
+      const [sellerEscrowPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("seller_escrow"), takerTimeslotPda.toBuffer(), seller.publicKey.toBuffer()],
+        program.programId
+      );
+      const reservePrice = new anchor.BN(0); // price taker
+      const [supplyLevelPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("supply_level"), takerTimeslotPda.toBuffer(), reservePrice.toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+
+      await program.methods
+        .commitSupply(takerEpoch, reservePrice, new anchor.BN(2))
+        .accounts({
+          globalState: globalStatePda,
+          timeslot: takerTimeslotPda,
+          supply: supplyPda,
+          supplyLevel: supplyLevelPda,
+          energyMint: energyMint.publicKey,
+          sellerSource: sellerEnergyAta,
+          sellerEscrow: sellerEscrowPda,
+          signer: seller.publicKey,
+          systemProgram: anchor.web3.SystemProgram.programId,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([seller])
+        .rpc();
+
+      const slot = await program.account.timeslot.fetch(takerTimeslotPda);
+      assert.isTrue(slot.minReservePrice.eq(reservePrice), "price taker sits at the bottom of the supply curve");
+    });
+
+    it("🚫 Fails to place a bid that does not beat the highest by the minimum increment", async () => {
+      const incEpoch = new anchor.BN(Date.now() + 20_000);
+      const [incTimeslotPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("timeslot"), incEpoch.toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+
+      const { pda: roundIndexPda } = await deriveNextRoundPda();
+
+      await program.methods
+        .openTimeslot(incEpoch, new anchor.BN(1), new anchor.BN(1_000_000), encodeMetadataUri(""), new anchor.BN(2_000_000), noLimits)
+        .accounts({
+          globalState: globalStatePda,
+          timeslot: incTimeslotPda,
+          roundIndex: roundIndexPda,
+          authority: authority.publicKey,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .rpc();
+
+      const [incTimeslotEscrow] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("quote_escrow"), incTimeslotPda.toBuffer()],
+        program.programId
+      );
+
+      const bidAt = async (price: anchor.BN) => {
+        const { pda: bidPagePda, pageIndex } = await deriveBidPagePda(incTimeslotPda);
+        await program.methods
+          .placeBid(pageIndex, price, new anchor.BN(1), new anchor.BN(Date.now()), new anchor.BN(0))
+          .accounts({
+            globalState: globalStatePda,
+            timeslot: incTimeslotPda,
+            timeslotQuoteEscrow: incTimeslotEscrow,
+            quoteMint: quoteMint.publicKey,
+            buyerSource: buyerQuoteAta,
+            buyer: buyer.publicKey,
+            bidPage: bidPagePda,
+            demandLevel: deriveDemandLevelPda(incTimeslotPda, price),
+            systemProgram: anchor.web3.SystemProgram.programId,
+            tokenProgram: TOKEN_PROGRAM_ID,
+          })
+          .signers([buyer])
+          .rpc();
+      };
+
+      await bidAt(new anchor.BN(5_000_000));
+
+      try {
+        await bidAt(new anchor.BN(6_000_000)); // only $1.00 above the $5.00 best, needs $2.00
+        assert.fail("Expected bid below the minimum increment to fail");
+      } catch (err) {
+        assert.instanceOf(err, AnchorError);
+        assert.equal((err as AnchorError).error.errorCode.code, "BidIncrementTooSmall");
+      }
+
+      await bidAt(new anchor.BN(7_000_000));
+      const slot = await program.account.timeslot.fetch(incTimeslotPda);
+      assert.isTrue(slot.highestBidPrice.eq(new anchor.BN(7_000_000)));
+    });
+
+    it("🚫 Fails to settle a timeslot that received supply but no bids", async () => {
+      const emptyEpoch = new anchor.BN(Date.now() + 40_000);
+      const [emptyTimeslotPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("timeslot"), emptyEpoch.toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+
+      const { pda: roundIndexPda } = await deriveNextRoundPda();
+
+      await program.methods
+        .openTimeslot(emptyEpoch, new anchor.BN(1), new anchor.BN(1_000_000), encodeMetadataUri(""), new anchor.BN(0), noLimits)
+        .accounts({
+          globalState: globalStatePda,
+          timeslot: emptyTimeslotPda,
+          roundIndex: roundIndexPda,
+          authority: authority.publicKey,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .rpc();
+
+      const [supplyPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("supply"), emptyTimeslotPda.toBuffer(), seller.publicKey.toBuffer()],
+        program.programId
+      );
+      const [sellerEscrowPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("seller_escrow"), emptyTimeslotPda.toBuffer(), seller.publicKey.toBuffer()],
+        program.programId
+      );
+      const reservePrice = new anchor.BN(1_000_000);
+      const [supplyLevelPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("supply_level"), emptyTimeslotPda.toBuffer(), reservePrice.toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+
+      await program.methods
+        .commitSupply(emptyEpoch, reservePrice, new anchor.BN(5))
+        .accounts({
+          globalState: globalStatePda,
+          timeslot: emptyTimeslotPda,
+          supply: supplyPda,
+          supplyLevel: supplyLevelPda,
+          energyMint: energyMint.publicKey,
+          sellerSource: sellerEnergyAta,
+          sellerEscrow: sellerEscrowPda,
+          signer: seller.publicKey,
+          systemProgram: anchor.web3.SystemProgram.programId,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([seller])
+        .rpc();
+
+      await program.methods
+        .sealTimeslot()
+        .accounts({
+          globalState: globalStatePda,
+          timeslot: emptyTimeslotPda,
+          authority: authority.publicKey,
+        })
+        .rpc();
+
+      try {
+        await program.methods
+          .settleTimeslot(new anchor.BN(1_000_000), new anchor.BN(0))
+          .accounts({
+            globalState: globalStatePda,
+            timeslot: emptyTimeslotPda,
+            authority: authority.publicKey,
+          })
+          .rpc();
+        assert.fail("Expected settling without bids to fail");
+      } catch (err) {
+        assert.instanceOf(err, AnchorError);
+        assert.equal((err as AnchorError).error.errorCode.code, "NoMarketClearing");
+      }
+    });
+
+    it("🚫 Fails to seal a timeslot before the minimum auction duration", async () => {
+      await program.methods
+        .setMinAuctionDuration(new anchor.BN(3600))
+        .accounts({
+          globalState: globalStatePda,
+          authority: authority.publicKey,
+        })
+        .rpc();
+
+      const earlyEpoch = new anchor.BN(Date.now() + 70_000);
+      const [earlyTimeslotPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("timeslot"), earlyEpoch.toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+
+      const { pda: roundIndexPda } = await deriveNextRoundPda();
+
+      await program.methods
+        .openTimeslot(earlyEpoch, new anchor.BN(1), new anchor.BN(1_000_000), encodeMetadataUri(""), new anchor.BN(0), noLimits)
+        .accounts({
+          globalState: globalStatePda,
+          timeslot: earlyTimeslotPda,
+          roundIndex: roundIndexPda,
+          authority: authority.publicKey,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .rpc();
+
+      try {
+        await program.methods
+          .sealTimeslot()
+          .accounts({
+            globalState: globalStatePda,
+            timeslot: earlyTimeslotPda,
+            authority: authority.publicKey,
+          })
+          .rpc();
+        assert.fail("Expected sealing before the minimum duration to fail");
+      } catch (err) {
+        assert.instanceOf(err, AnchorError);
+        assert.equal((err as AnchorError).error.errorCode.code, "AuctionTooShort");
+      } finally {
+        // restore so later tests can seal immediately
+        await program.methods
+          .setMinAuctionDuration(new anchor.BN(0))
+          .accounts({
+            globalState: globalStatePda,
+            authority: authority.publicKey,
+          })
+          .rpc();
+      }
+    });
+
+    it("🚫 Rejects a retried bid carrying the same nonce", async () => {
+      const nonceEpoch = new anchor.BN(Date.now() + 80_000);
+      const [nonceTimeslotPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("timeslot"), nonceEpoch.toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+
+      const { pda: roundIndexPda } = await deriveNextRoundPda();
+
+      await program.methods
+        .openTimeslot(nonceEpoch, new anchor.BN(1), new anchor.BN(1_000_000), encodeMetadataUri(""), new anchor.BN(0), noLimits)
+        .accounts({
+          globalState: globalStatePda,
+          timeslot: nonceTimeslotPda,
+          roundIndex: roundIndexPda,
+          authority: authority.publicKey,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .rpc();
+
+      const [nonceTimeslotEscrow] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("quote_escrow"), nonceTimeslotPda.toBuffer()],
+        program.programId
+      );
+
+      const bidWithNonce = async (nonce: anchor.BN) => {
+        const { pda: bidPagePda, pageIndex } = await deriveBidPagePda(nonceTimeslotPda);
+        await program.methods
+          .placeBid(pageIndex, new anchor.BN(2_000_000), new anchor.BN(1), new anchor.BN(Date.now()), nonce)
+          .accounts({
+            globalState: globalStatePda,
+            timeslot: nonceTimeslotPda,
+            timeslotQuoteEscrow: nonceTimeslotEscrow,
+            quoteMint: quoteMint.publicKey,
+            buyerSource: buyerQuoteAta,
+            buyer: buyer.publicKey,
+            bidPage: bidPagePda,
+            demandLevel: deriveDemandLevelPda(nonceTimeslotPda, new anchor.BN(2_000_000)),
+            systemProgram: anchor.web3.SystemProgram.programId,
+            tokenProgram: TOKEN_PROGRAM_ID,
+          })
+          .signers([buyer])
+          .rpc();
+      };
+
+      await bidWithNonce(new anchor.BN(42));
+      const escrowAfterFirst = (await getAccount(provider.connection, nonceTimeslotEscrow)).amount;
+
+      try {
+        await bidWithNonce(new anchor.BN(42));
+        assert.fail("Expected the retried bid to be rejected");
+      } catch (err) {
+        assert.instanceOf(err, AnchorError);
+        assert.equal((err as AnchorError).error.errorCode.code, "DuplicateBid");
+      }
+
+      const escrowAfterRetry = (await getAccount(provider.connection, nonceTimeslotEscrow)).amount;
+      assert.equal(escrowAfterRetry.toString(), escrowAfterFirst.toString(), "no double escrow");
+    });
+
+    it("🚫 Fails to settle when the lowest reserve is above the highest bid", async () => {
+      const disjointEpoch = new anchor.BN(Date.now() + 90_000);
+      const [timeslotPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("timeslot"), disjointEpoch.toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      const [timeslotQuoteEscrow] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("quote_escrow"), timeslotPda.toBuffer()],
+        program.programId
+      );
+      const [supplyPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("supply"), timeslotPda.toBuffer(), seller.publicKey.toBuffer()],
+        program.programId
+      );
+      const [sellerEscrowPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("seller_escrow"), timeslotPda.toBuffer(), seller.publicKey.toBuffer()],
+        program.programId
+      );
+      const reservePrice = new anchor.BN(5_000_000);
+      const bidPrice = new anchor.BN(2_000_000);
+      const [supplyLevelPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("supply_level"), timeslotPda.toBuffer(), reservePrice.toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+
+      const { pda: roundIndexPda } = await deriveNextRoundPda();
+      await program.methods
+        .openTimeslot(disjointEpoch, new anchor.BN(1), new anchor.BN(1_000_000), encodeMetadataUri(""), new anchor.BN(0), noLimits)
+        .accounts({
+          globalState: globalStatePda,
+          timeslot: timeslotPda,
+          roundIndex: roundIndexPda,
+          authority: authority.publicKey,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .rpc();
+
+      await program.methods
+        .commitSupply(disjointEpoch, reservePrice, new anchor.BN(3))
+        .accounts({
+          globalState: globalStatePda,
+          timeslot: timeslotPda,
+          supply: supplyPda,
+          supplyLevel: supplyLevelPda,
+          energyMint: energyMint.publicKey,
+          sellerSource: sellerEnergyAta,
+          sellerEscrow: sellerEscrowPda,
+          signer: seller.publicKey,
+          systemProgram: anchor.web3.SystemProgram.programId,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([seller])
+        .rpc();
+
+      const { pda: bidPagePda, pageIndex } = await deriveBidPagePda(timeslotPda);
+      await program.methods
+        .placeBid(pageIndex, bidPrice, new anchor.BN(3), new anchor.BN(Date.now()), new anchor.BN(0))
+        .accounts({
+          globalState: globalStatePda,
+          timeslot: timeslotPda,
+          timeslotQuoteEscrow,
+          quoteMint: quoteMint.publicKey,
+          buyerSource: buyerQuoteAta,
+          buyer: buyer.publicKey,
+          bidPage: bidPagePda,
+          demandLevel: deriveDemandLevelPda(timeslotPda, bidPrice),
+          systemProgram: anchor.web3.SystemProgram.programId,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([buyer])
+        .rpc();
+
+      await program.methods
+        .sealTimeslot()
+        .accounts({
+          globalState: globalStatePda,
+          timeslot: timeslotPda,
+          authority: authority.publicKey,
+        })
+        .rpc();
+
+      try {
+        await program.methods
+          .settleTimeslot(bidPrice, new anchor.BN(3))
+          .accounts({
+            globalState: globalStatePda,
+            timeslot: timeslotPda,
+            authority: authority.publicKey,
+          })
+          .rpc();
+        assert.fail("Expected disjoint curves to fail settlement");
+      } catch (err) {
+        assert.instanceOf(err, AnchorError);
+        assert.equal((err as AnchorError).error.errorCode.code, "NoIntersection");
+      }
+
+      // the slot can never clear, so it is cancelled and both sides get their funds back
+      await program.methods
+        .cancelTimeslot()
+        .accounts({
+          globalState: globalStatePda,
+          timeslot: timeslotPda,
+          authority: authority.publicKey,
+        })
+        .rpc();
+      const next = await program.methods
+        .getNextAction()
+        .accounts({ globalState: globalStatePda, timeslot: timeslotPda })
+        .view();
+      assert.deepEqual(next.action, { needsRefund: {} });
+
+      const buyerQuoteBefore = (await getAccount(provider.connection, buyerQuoteAta)).amount;
+      await program.methods
+        .cancelBid(pageIndex, 0)
+        .accounts({
+          timeslot: timeslotPda,
+          bidPage: bidPagePda,
+          demandLevel: deriveDemandLevelPda(timeslotPda, bidPrice),
+          timeslotQuoteEscrow,
+          buyerQuoteAta,
+          buyer: buyer.publicKey,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([buyer])
+        .rpc();
+      const buyerQuoteAfter = (await getAccount(provider.connection, buyerQuoteAta)).amount;
+      assert.equal((buyerQuoteAfter - buyerQuoteBefore).toString(), bidPrice.mul(new anchor.BN(3)).toString());
+
+      const sellerEnergyBefore = (await getAccount(provider.connection, sellerEnergyAta)).amount;
+      await program.methods
+        .withdrawSupply()
+        .accounts({
+          timeslot: timeslotPda,
+          supply: supplyPda,
+          supplyLevel: supplyLevelPda,
+          sellerEscrow: sellerEscrowPda,
+          sellerDestination: sellerEnergyAta,
+          seller: seller.publicKey,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([seller])
+        .rpc();
+      const sellerEnergyAfter = (await getAccount(provider.connection, sellerEnergyAta)).amount;
+      assert.equal((sellerEnergyAfter - sellerEnergyBefore).toString(), "3");
+    });
+
+    it("🚫 Fails to place a bid with insufficient buyer balance", async () => {
+      // create new timeslot for this negative test
+      const newEpoch = new anchor.BN(Date.now() + 10_000);
+      const [newTimeslotPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("timeslot"), newEpoch.toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+  
+      const { pda: roundIndexPda } = await deriveNextRoundPda();
+
+      await program.methods
+        .openTimeslot(newEpoch, new anchor.BN(1), new anchor.BN(1_000_000), encodeMetadataUri(""), new anchor.BN(0), noLimits)
+        .accounts({
+          globalState: globalStatePda,
+          timeslot: newTimeslotPda,
+          roundIndex: roundIndexPda,
+          authority: authority.publicKey,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .rpc();
+  
+      const [newTimeslotEscrow] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("quote_escrow"), newTimeslotPda.toBuffer()],
+        program.programId
+      );
+  
+      const { pda: bidPagePda, pageIndex } = await deriveBidPagePda(newTimeslotPda);
+  
+      const price = new anchor.BN(1_000_000); // $1.00
+      const quantity = new anchor.BN(10_000); // requires 10,000 USDC, poorBuyer has 0.001
+      const timestamp = new anchor.BN(Date.now());
+  
+      try {
+        await program.methods
+          .placeBid(pageIndex, price, quantity, timestamp, new anchor.BN(0))
+          .accounts({
+            globalState: globalStatePda,
+            timeslot: newTimeslotPda,
+            timeslotQuoteEscrow: newTimeslotEscrow,
+            quoteMint: quoteMint.publicKey,
+            buyerSource: poorBuyerQuoteAta,
+            buyer: poorBuyer.publicKey,
+            bidPage: bidPagePda,
+            demandLevel: deriveDemandLevelPda(newTimeslotPda, price),
+            systemProgram: anchor.web3.SystemProgram.programId,
+            tokenProgram: TOKEN_PROGRAM_ID,
+          })
+          .signers([poorBuyer])
+          .rpc();
+        assert.fail("Expected bid to fail due to insufficient funds");
+      } catch (err) {
+        // balance is checked before the token CPI, so this is a program error rather than an SPL one
+        assert.instanceOf(err, AnchorError);
+        assert.equal((err as AnchorError).error.errorCode.code, "InsufficientBalance");
+      }
+    });
+  });
+
+  describe("Boundary Pricing", () => {
+    it("✅ Treats a bid and a reserve exactly at the clearing price as winning", async () => {
+      const boundaryEpoch = new anchor.BN(Date.now() + 60_000);
+      const [timeslotPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("timeslot"), boundaryEpoch.toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      const [timeslotQuoteEscrow] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("quote_escrow"), timeslotPda.toBuffer()],
+        program.programId
+      );
+      const [supplyPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("supply"), timeslotPda.toBuffer(), seller.publicKey.toBuffer()],
+        program.programId
+      );
+      const [sellerEscrowPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("seller_escrow"), timeslotPda.toBuffer(), seller.publicKey.toBuffer()],
+        program.programId
+      );
+
+      const price = new anchor.BN(10_000_000); // bid = reserve = clearing
+      const quantity = new anchor.BN(5);
+      const [supplyLevelPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("supply_level"), timeslotPda.toBuffer(), price.toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+
+      const { pda: roundIndexPda } = await deriveNextRoundPda();
+      await program.methods
+        .openTimeslot(boundaryEpoch, new anchor.BN(1), new anchor.BN(1_000_000), encodeMetadataUri(""), new anchor.BN(0), noLimits)
+        .accounts({
+          globalState: globalStatePda,
+          timeslot: timeslotPda,
+          roundIndex: roundIndexPda,
+          authority: authority.publicKey,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .rpc();
+
+      await program.methods
+        .commitSupply(boundaryEpoch, price, quantity)
+        .accounts({
+          globalState: globalStatePda,
+          timeslot: timeslotPda,
+          supply: supplyPda,
+          supplyLevel: supplyLevelPda,
+          energyMint: energyMint.publicKey,
+          sellerSource: sellerEnergyAta,
+          sellerEscrow: sellerEscrowPda,
+          signer: seller.publicKey,
+          systemProgram: anchor.web3.SystemProgram.programId,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([seller])
+        .rpc();
+
+      const { pda: bidPagePda, pageIndex } = await deriveBidPagePda(timeslotPda);
+      await program.methods
+        .placeBid(pageIndex, price, quantity, new anchor.BN(Date.now()), new anchor.BN(0))
+        .accounts({
+          globalState: globalStatePda,
+          timeslot: timeslotPda,
+          timeslotQuoteEscrow,
+          quoteMint: quoteMint.publicKey,
+          buyerSource: buyerQuoteAta,
+          buyer: buyer.publicKey,
+          bidPage: bidPagePda,
+          demandLevel: deriveDemandLevelPda(timeslotPda, price),
+          systemProgram: anchor.web3.SystemProgram.programId,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([buyer])
+        .rpc();
+
+      await program.methods
+        .sealTimeslot()
+        .accounts({
+          globalState: globalStatePda,
+          timeslot: timeslotPda,
+          authority: authority.publicKey,
+        })
+        .rpc();
+
+      await program.methods
+        .settleTimeslot(price, quantity)
+        .accounts({
+          globalState: globalStatePda,
+          timeslot: timeslotPda,
+          authority: authority.publicKey,
+        })
+        .remainingAccounts([{ pubkey: supplyLevelPda, isSigner: false, isWritable: false }])
+        .rpc();
+
+      const sellerQuoteBefore = (await getAccount(provider.connection, sellerQuoteAta)).amount;
+
+      await program.methods
+        .withdrawProceeds()
+        .accounts({
+          globalState: globalStatePda,
+          timeslot: timeslotPda,
+          supply: supplyPda,
+          timeslotQuoteEscrow,
+          feeVault: feeVaultPda,
+          sellerProceedsAta: sellerQuoteAta,
+          seller: seller.publicKey,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([seller])
+        .rpc();
+
+      const sellerQuoteAfter = (await getAccount(provider.connection, sellerQuoteAta)).amount;
+      assert.isTrue(sellerQuoteAfter > sellerQuoteBefore, "Reserve at the clearing price is accepted");
+
+      const updatedSupply = await program.account.supply.fetch(supplyPda);
+      assert.isTrue(updatedSupply.claimed);
+    });
+  });
+
+  describe("On-chain Clearing", () => {
+    type ClearingSeller = { kp: anchor.web3.Keypair; energyAta: anchor.web3.PublicKey; reserve: number; amount: number };
+    type OrderBook = Awaited<ReturnType<typeof openBook>>;
+
+    const levelAccounts = (keys: anchor.web3.PublicKey[]) =>
+      keys.map((pubkey) => ({ pubkey, isSigner: false, isWritable: false }));
+
+    // Opens a fresh timeslot, commits each seller's supply and places one bid per
+    // (price, quantity) from the main buyer. The timeslot is left Open.
+    const openBook = async (
+      epoch: anchor.BN,
+      sellers: ClearingSeller[],
+      bids: { price: number; quantity: number }[]
+    ) => {
+      const [timeslotPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("timeslot"), epoch.toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      const [timeslotQuoteEscrow] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("quote_escrow"), timeslotPda.toBuffer()],
+        program.programId
+      );
+      const supplyLevelPda = (reserve: number) =>
+        anchor.web3.PublicKey.findProgramAddressSync(
+          [Buffer.from("supply_level"), timeslotPda.toBuffer(), new anchor.BN(reserve).toArrayLike(Buffer, "le", 8)],
+          program.programId
+        )[0];
+
+      const { pda: roundIndexPda } = await deriveNextRoundPda();
+      await program.methods
+        .openTimeslot(epoch, new anchor.BN(1), new anchor.BN(1_000_000), encodeMetadataUri(""), new anchor.BN(0), noLimits)
+        .accounts({
+          globalState: globalStatePda,
+          timeslot: timeslotPda,
+          roundIndex: roundIndexPda,
+          authority: authority.publicKey,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .rpc();
+
+      const supplyPdas: anchor.web3.PublicKey[] = [];
+      const sellerEscrows: anchor.web3.PublicKey[] = [];
+      for (const s of sellers) {
+        const [supplyPda] = anchor.web3.PublicKey.findProgramAddressSync(
+          [Buffer.from("supply"), timeslotPda.toBuffer(), s.kp.publicKey.toBuffer()],
+          program.programId
+        );
+        const [sellerEscrowPda] = anchor.web3.PublicKey.findProgramAddressSync(
+          [Buffer.from("seller_escrow"), timeslotPda.toBuffer(), s.kp.publicKey.toBuffer()],
+          program.programId
+        );
+        await program.methods
+          .commitSupply(epoch, new anchor.BN(s.reserve), new anchor.BN(s.amount))
+          .accounts({
+            globalState: globalStatePda,
+            timeslot: timeslotPda,
+            supply: supplyPda,
+            supplyLevel: supplyLevelPda(s.reserve),
+            energyMint: energyMint.publicKey,
+            sellerSource: s.energyAta,
+            sellerEscrow: sellerEscrowPda,
+            signer: s.kp.publicKey,
+            systemProgram: anchor.web3.SystemProgram.programId,
+            tokenProgram: TOKEN_PROGRAM_ID,
+          })
+          .signers([s.kp])
+          .rpc();
+        supplyPdas.push(supplyPda);
+        sellerEscrows.push(sellerEscrowPda);
+      }
+
+      const { pda: bidPagePda, pageIndex } = await deriveBidPagePda(timeslotPda);
+      for (const b of bids) {
+        await program.methods
+          .placeBid(pageIndex, new anchor.BN(b.price), new anchor.BN(b.quantity), new anchor.BN(Date.now()), new anchor.BN(0))
+          .accounts({
+            globalState: globalStatePda,
+            timeslot: timeslotPda,
+            timeslotQuoteEscrow,
+            quoteMint: quoteMint.publicKey,
+            buyerSource: buyerQuoteAta,
+            buyer: buyer.publicKey,
+            bidPage: bidPagePda,
+            demandLevel: deriveDemandLevelPda(timeslotPda, new anchor.BN(b.price)),
+            systemProgram: anchor.web3.SystemProgram.programId,
+            tokenProgram: TOKEN_PROGRAM_ID,
+          })
+          .signers([buyer])
+          .rpc();
+      }
+
+      return {
+        timeslotPda,
+        timeslotQuoteEscrow,
+        supplyPdas,
+        sellerEscrows,
+        bidPagePda,
+        pageIndex,
+        supplyLevels: [...new Set(sellers.map((s) => s.reserve))].map(supplyLevelPda),
+        demandLevels: [...new Set(bids.map((b) => b.price))].map((p) =>
+          deriveDemandLevelPda(timeslotPda, new anchor.BN(p))
+        ),
+      };
+    };
+
+    const sealBook = (book: OrderBook) =>
+      program.methods
+        .sealTimeslot()
+        .accounts({
+          globalState: globalStatePda,
+          timeslot: book.timeslotPda,
+          authority: authority.publicKey,
+        })
+        .rpc();
+
+    // supply levels first, then demand levels; override either to build a bad book
+    const executeClearing = (
+      book: OrderBook,
+      supplyLevels = book.supplyLevels,
+      demandLevels = book.demandLevels
+    ) =>
+      program.methods
+        .executeAuctionClearing(supplyLevels.length)
+        .accounts({
+          globalState: globalStatePda,
+          timeslot: book.timeslotPda,
+          authority: authority.publicKey,
+        })
+        .remainingAccounts(levelAccounts([...supplyLevels, ...demandLevels]))
+        .rpc();
+
+    // openBook, then seal and clear it on-chain, or settle it manually when
+    // `settleWith` is given.
+    const runClearing = async (
+      epoch: anchor.BN,
+      sellers: ClearingSeller[],
+      bids: { price: number; quantity: number }[],
+      settleWith?: { price: number; quantity: number }
+    ) => {
+      const book = await openBook(epoch, sellers, bids);
+      await sealBook(book);
+      if (settleWith) {
+        await program.methods
+          .settleTimeslot(new anchor.BN(settleWith.price), new anchor.BN(settleWith.quantity))
+          .accounts({
+            globalState: globalStatePda,
+            timeslot: book.timeslotPda,
+            authority: authority.publicKey,
+          })
+          .remainingAccounts(levelAccounts(book.supplyLevels))
+          .rpc();
+      } else {
+        await executeClearing(book);
+      }
+      return book;
+    };
+
+    it("✅ Only the top half by price wins when demand is twice supply", async () => {
+      const { timeslotPda } = await runClearing(
+        new anchor.BN(Date.now() + 240_000),
+        [{ kp: seller, energyAta: sellerEnergyAta, reserve: 2_000_000, amount: 10 }],
+        [
+          { price: 5_000_000, quantity: 10 },
+          { price: 4_000_000, quantity: 10 },
+        ]
+      );
+
+      // the 5-bids take all 10 lots; clearing at 4 would leave the 4-bids unfilled
+      const ts = await program.account.timeslot.fetch(timeslotPda);
+      assert.equal(ts.clearingPrice.toNumber(), 5_000_000);
+      assert.equal(ts.totalSoldQuantity.toNumber(), 10);
+    });
+
+    it("✅ Clears all demand and leaves unmet supply unallocated when supply is twice demand", async () => {
+      const highReserveSeller = anchor.web3.Keypair.generate();
+      await airdropAndConfirm(highReserveSeller.publicKey, 1 * anchor.web3.LAMPORTS_PER_SOL);
+      const highReserveAta = await createAssociatedTokenAccount(
+        provider.connection,
+        highReserveSeller,
+        energyMint.publicKey,
+        highReserveSeller.publicKey
+      );
+      const highReserveQuoteAta = await createAssociatedTokenAccount(
+        provider.connection,
+        highReserveSeller,
+        quoteMint.publicKey,
+        highReserveSeller.publicKey
+      );
+      await mintTo(
+        provider.connection,
+        authority.payer,
+        energyMint.publicKey,
+        highReserveAta,
+        authority.publicKey,
+        10
+      );
+
+      const { timeslotPda, timeslotQuoteEscrow, supplyPdas, sellerEscrows } = await runClearing(
+        new anchor.BN(Date.now() + 270_000),
+        [
+          { kp: seller, energyAta: sellerEnergyAta, reserve: 2_000_000, amount: 10 },
+          { kp: highReserveSeller, energyAta: highReserveAta, reserve: 6_000_000, amount: 10 },
+        ],
+        [{ price: 5_000_000, quantity: 10 }]
+      );
+
+      const ts = await program.account.timeslot.fetch(timeslotPda);
+      assert.equal(ts.clearingPrice.toNumber(), 5_000_000);
+      assert.equal(ts.totalSoldQuantity.toNumber(), 10, "cleared quantity equals total demand");
+      assert.equal(ts.clearedSupply.toNumber(), 10, "only supply with a met reserve shares the sale");
+
+      const accepted = await program.methods
+        .previewProceeds()
+        .accounts({ globalState: globalStatePda, timeslot: timeslotPda, supply: supplyPdas[0] })
+        .view();
+      assert.equal(accepted.allocatedQuantity.toNumber(), 10);
+
+      try {
+        await program.methods
+          .withdrawProceeds()
+          .accounts({
+            globalState: globalStatePda,
+            timeslot: timeslotPda,
+            supply: supplyPdas[1],
+            timeslotQuoteEscrow,
+            feeVault: feeVaultPda,
+            sellerProceedsAta: highReserveQuoteAta,
+            seller: highReserveSeller.publicKey,
+            tokenProgram: TOKEN_PROGRAM_ID,
+          })
+          .signers([highReserveSeller])
+          .rpc();
+        assert.fail("Expected the seller above the clearing price to get no allocation");
+      } catch (err) {
+        assert.instanceOf(err, AnchorError);
+        assert.equal((err as AnchorError).error.errorCode.code, "ReserveNotMet");
+      }
+
+      // nor can the winning buyer take delivery out of that seller's escrow
+      const [fillReceiptPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("fill_receipt"), timeslotPda.toBuffer(), buyer.publicKey.toBuffer()],
+        program.programId
+      );
+      await program.methods
+        .createFillReceipt(new anchor.BN(10))
+        .accounts({
+          globalState: globalStatePda,
+          timeslot: timeslotPda,
+          buyer: buyer.publicKey,
+          fillReceipt: fillReceiptPda,
+          authority: authority.publicKey,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .rpc();
+      try {
+        await program.methods
+          .redeemEnergyAndRefund(new anchor.BN(5_000_000 * 10))
+          .accounts({
+            globalState: globalStatePda,
+            timeslot: timeslotPda,
+            fillReceipt: fillReceiptPda,
+            timeslotQuoteEscrow,
+            buyerQuoteAta,
+            buyerEnergyAta,
+            supply: supplyPdas[1],
+            sellerEscrow: sellerEscrows[1],
+            buyer: buyer.publicKey,
+            tokenProgram: TOKEN_PROGRAM_ID,
+          })
+          .signers([buyer])
+          .rpc();
+        assert.fail("Expected redemption from a seller with an unmet reserve to fail");
+      } catch (err) {
+        assert.instanceOf(err, AnchorError);
+        assert.equal((err as AnchorError).error.errorCode.code, "ReserveNotMet");
+      }
+
+      // the unmatched seller takes all of their energy back instead
+      await program.methods
+        .reclaimUnsoldEnergy()
+        .accounts({
+          timeslot: timeslotPda,
+          supply: supplyPdas[1],
+          sellerEscrow: sellerEscrows[1],
+          sellerDestination: highReserveAta,
+          seller: highReserveSeller.publicKey,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([highReserveSeller])
+        .rpc();
+      assert.equal((await getAccount(provider.connection, highReserveAta)).amount.toString(), "10");
+    });
+
+    it("✅ Fills price takers before priced sellers in merit order", async () => {
+      const priceTaker = anchor.web3.Keypair.generate();
+      await airdropAndConfirm(priceTaker.publicKey, 1 * anchor.web3.LAMPORTS_PER_SOL);
+      const takerEnergyAta = await createAssociatedTokenAccount(
+        provider.connection,
+        priceTaker,
+        energyMint.publicKey,
+        priceTaker.publicKey
+      );
+      const takerQuoteAta = await createAssociatedTokenAccount(
+        provider.connection,
+        priceTaker,
+        quoteMint.publicKey,
+        priceTaker.publicKey
+      );
+      await mintTo(
+        provider.connection,
+        authority.payer,
+        energyMint.publicKey,
+        takerEnergyAta,
+        authority.publicKey,
+        4
+      );
+
+      const clearing = new anchor.BN(3_000_000);
+      const { timeslotPda, timeslotQuoteEscrow, supplyPdas } = await runClearing(
+        new anchor.BN(Date.now() + 330_000),
+        [
+          { kp: priceTaker, energyAta: takerEnergyAta, reserve: 0, amount: 4 },
+          { kp: seller, energyAta: sellerEnergyAta, reserve: 2_000_000, amount: 4 },
+        ],
+        [{ price: clearing.toNumber(), quantity: 6 }]
+      );
+
+      // 6 sold: the price taker's 4 lots fill first, the priced seller is marginal with 2
+      const ts = await program.account.timeslot.fetch(timeslotPda);
+      assert.isTrue(ts.clearingPrice.eq(clearing));
+      assert.equal(ts.totalSoldQuantity.toNumber(), 6);
+      assert.equal(ts.clearedSupply.toNumber(), 8);
+      assert.equal(ts.marginalReserve.toNumber(), 2_000_000);
+      assert.equal(ts.marginalSold.toNumber(), 2);
+
+      const priced = await program.methods
+        .previewProceeds()
+        .accounts({ globalState: globalStatePda, timeslot: timeslotPda, supply: supplyPdas[1] })
+        .view();
+      assert.equal(priced.allocatedQuantity.toNumber(), 2);
+
+      const globalState = await program.account.globalState.fetch(globalStatePda);
+      const gross = clearing.mul(new anchor.BN(4));
+      const fee = gross.mul(new anchor.BN(globalState.feeBps)).div(new anchor.BN(10000));
+
+      const before = (await getAccount(provider.connection, takerQuoteAta)).amount;
+      await program.methods
+        .withdrawProceeds()
+        .accounts({
+          globalState: globalStatePda,
+          timeslot: timeslotPda,
+          supply: supplyPdas[0],
+          timeslotQuoteEscrow,
+          feeVault: feeVaultPda,
+          sellerProceedsAta: takerQuoteAta,
+          seller: priceTaker.publicKey,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([priceTaker])
+        .rpc();
+      const after = (await getAccount(provider.connection, takerQuoteAta)).amount;
+      assert.equal((after - before).toString(), gross.sub(fee).toString());
+    });
+
+    it("✅ Manual settlement leaves sellers with an unmet reserve out of the pro-rata base", async () => {
+      const highReserveSeller = anchor.web3.Keypair.generate();
+      await airdropAndConfirm(highReserveSeller.publicKey, 1 * anchor.web3.LAMPORTS_PER_SOL);
+      const highReserveAta = await createAssociatedTokenAccount(
+        provider.connection,
+        highReserveSeller,
+        energyMint.publicKey,
+        highReserveSeller.publicKey
+      );
+      await mintTo(
+        provider.connection,
+        authority.payer,
+        energyMint.publicKey,
+        highReserveAta,
+        authority.publicKey,
+        10
+      );
+
+      const { timeslotPda, supplyPdas } = await runClearing(
+        new anchor.BN(Date.now() + 300_000),
+        [
+          { kp: seller, energyAta: sellerEnergyAta, reserve: 2_000_000, amount: 10 },
+          { kp: highReserveSeller, energyAta: highReserveAta, reserve: 6_000_000, amount: 10 },
+        ],
+        [{ price: 5_000_000, quantity: 10 }],
+        { price: 5_000_000, quantity: 10 }
+      );
+
+      const ts = await program.account.timeslot.fetch(timeslotPda);
+      assert.equal(ts.clearedSupply.toNumber(), 10);
+
+      // the accepted seller is paid for the whole sale instead of half of it
+      const accepted = await program.methods
+        .previewProceeds()
+        .accounts({ globalState: globalStatePda, timeslot: timeslotPda, supply: supplyPdas[0] })
+        .view();
+      assert.equal(accepted.allocatedQuantity.toNumber(), 10);
+    });
+
+    it("✅ Clears at the lowest price that fills the most supply", async () => {
+      // 6 lots at each of 5, 4 and 3: demand reaches 10 lots of supply at 4
+      const { timeslotPda } = await runClearing(
+        new anchor.BN(Date.now() + 120_000),
+        [{ kp: seller, energyAta: sellerEnergyAta, reserve: 2_000_000, amount: 10 }],
+        [
+          { price: 5_000_000, quantity: 6 },
+          { price: 4_000_000, quantity: 6 },
+          { price: 3_000_000, quantity: 6 },
+        ]
+      );
+
+      const ts = await program.account.timeslot.fetch(timeslotPda);
+      assert.equal(ts.status, 3); // Settled
+      assert.equal(ts.clearingPrice.toNumber(), 4_000_000);
+      assert.equal(ts.totalSoldQuantity.toNumber(), 10);
+    });
+
+    it("✅ Refunds a cancelled bid and leaves it out of clearing", async () => {
+      const book = await openBook(
+        new anchor.BN(Date.now() + 150_000),
+        [{ kp: seller, energyAta: sellerEnergyAta, reserve: 2_000_000, amount: 10 }],
+        [
+          { price: 5_000_000, quantity: 6 },
+          { price: 4_000_000, quantity: 6 },
+        ]
+      );
+
+      const cancelAccounts = {
+        timeslot: book.timeslotPda,
+        bidPage: book.bidPagePda,
+        demandLevel: book.demandLevels[0],
+        timeslotQuoteEscrow: book.timeslotQuoteEscrow,
+        buyerQuoteAta,
+        buyer: buyer.publicKey,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      };
+      const balanceBefore = (await getAccount(provider.connection, buyerQuoteAta)).amount;
+      await program.methods
+        .cancelBid(book.pageIndex, 0)
+        .accounts(cancelAccounts)
+        .signers([buyer])
+        .rpc();
+
+      const balanceAfter = (await getAccount(provider.connection, buyerQuoteAta)).amount;
+      assert.equal((balanceAfter - balanceBefore).toString(), (5_000_000 * 6).toString());
+      const page = await program.account.bidPage.fetch(book.bidPagePda);
+      assert.equal(page.bids[0].status, 1); // Cancelled
+      let ts = await program.account.timeslot.fetch(book.timeslotPda);
+      assert.equal(ts.totalBids.toNumber(), 6);
+      const cancelledLevel = await program.account.demandLevelAggregate.fetch(cancelAccounts.demandLevel);
+      assert.equal(cancelledLevel.totalQuantity.toNumber(), 0);
+      assert.equal(cancelledLevel.bidCount, 0);
+
+      try {
+        await program.methods
+          .cancelBid(book.pageIndex, 0)
+          .accounts(cancelAccounts)
+          .signers([buyer])
+          .rpc();
+        assert.fail("Expected a second cancellation to fail");
+      } catch (err) {
+        assert.instanceOf(err, AnchorError);
+        assert.equal((err as AnchorError).error.errorCode.code, "BidNotActive");
+      }
+
+      await sealBook(book);
+      await executeClearing(book);
+
+      ts = await program.account.timeslot.fetch(book.timeslotPda);
+      assert.equal(ts.clearingPrice.toNumber(), 4_000_000);
+      assert.equal(ts.totalSoldQuantity.toNumber(), 6);
+    });
+
+    const expectClearingError = async (call: Promise<unknown>, code: string) => {
+      try {
+        await call;
+        assert.fail(`Expected clearing to fail with ${code}`);
+      } catch (err) {
+        assert.instanceOf(err, AnchorError);
+        assert.equal((err as AnchorError).error.errorCode.code, code);
+      }
+    };
+
+    it("🚫 Fails to clear an empty order book", async () => {
+      const book = await openBook(
+        new anchor.BN(Date.now() + 450_000),
+        [{ kp: seller, energyAta: sellerEnergyAta, reserve: 2_000_000, amount: 4 }],
+        []
+      );
+      await sealBook(book);
+      await expectClearingError(executeClearing(book), "NoMarketClearing");
+    });
+
+    it("🚫 Fails to clear when every reserve is above every bid", async () => {
+      const book = await openBook(
+        new anchor.BN(Date.now() + 480_000),
+        [{ kp: seller, energyAta: sellerEnergyAta, reserve: 6_000_000, amount: 4 }],
+        [{ price: 2_000_000, quantity: 4 }]
+      );
+      await sealBook(book);
+      await expectClearingError(executeClearing(book), "NoIntersection");
+    });
+
+    it("🚫 Fails to clear an incomplete or padded order book", async () => {
+      const book = await openBook(
+        new anchor.BN(Date.now() + 510_000),
+        [{ kp: seller, energyAta: sellerEnergyAta, reserve: 2_000_000, amount: 10 }],
+        [
+          { price: 5_000_000, quantity: 6 },
+          { price: 4_000_000, quantity: 6 },
+        ]
+      );
+      await sealBook(book);
+      const [top, next] = book.demandLevels;
+
+      // omitting a level would let the authority clear against part of the book
+      await expectClearingError(executeClearing(book, book.supplyLevels, [top]), "IncompleteOrderBook");
+      // nor can the total be padded by passing a level twice
+      await expectClearingError(executeClearing(book, book.supplyLevels, [top, top, next]), "ConstraintViolation");
+      // or by counting a level of another timeslot
+      const other = await openBook(
+        new anchor.BN(Date.now() + 540_000),
+        [{ kp: seller, energyAta: sellerEnergyAta, reserve: 2_000_000, amount: 1 }],
+        [{ price: 4_000_000, quantity: 1 }]
+      );
+      await expectClearingError(
+        executeClearing(book, book.supplyLevels, [top, other.demandLevels[0]]),
+        "ConstraintViolation"
+      );
+
+      // the full book still clears
+      await executeClearing(book);
+      const ts = await program.account.timeslot.fetch(book.timeslotPda);
+      assert.equal(ts.clearingPrice.toNumber(), 4_000_000);
+    });
+  });
+
+  describe("Supply Withdrawal", () => {
+    const withdrawEpoch = new anchor.BN(Date.now() + 180_000);
+    const reservePrice = new anchor.BN(1_000_000);
+    let timeslotPda: anchor.web3.PublicKey;
+    let supplyPda: anchor.web3.PublicKey;
+    let supplyLevelPda: anchor.web3.PublicKey;
+    let sellerEscrowPda: anchor.web3.PublicKey;
+
+    const commitAccounts = () => ({
+      globalState: globalStatePda,
+      timeslot: timeslotPda,
+      supply: supplyPda,
+      supplyLevel: supplyLevelPda,
+      energyMint: energyMint.publicKey,
+      sellerSource: sellerEnergyAta,
+      sellerEscrow: sellerEscrowPda,
+      signer: seller.publicKey,
+      systemProgram: anchor.web3.SystemProgram.programId,
+      tokenProgram: TOKEN_PROGRAM_ID,
+    });
+
+    const withdrawAccounts = () => ({
+      timeslot: timeslotPda,
+      supply: supplyPda,
+      supplyLevel: supplyLevelPda,
+      sellerEscrow: sellerEscrowPda,
+      sellerDestination: sellerEnergyAta,
+      seller: seller.publicKey,
+      tokenProgram: TOKEN_PROGRAM_ID,
+    });
+
+    it("✅ Withdraws committed supply while the timeslot is Open", async () => {
+      [timeslotPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("timeslot"), withdrawEpoch.toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      [supplyPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("supply"), timeslotPda.toBuffer(), seller.publicKey.toBuffer()],
+        program.programId
+      );
+      [sellerEscrowPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("seller_escrow"), timeslotPda.toBuffer(), seller.publicKey.toBuffer()],
+        program.programId
+      );
+      [supplyLevelPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("supply_level"), timeslotPda.toBuffer(), reservePrice.toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+
+      const { pda: roundIndexPda } = await deriveNextRoundPda();
+      await program.methods
+        .openTimeslot(withdrawEpoch, new anchor.BN(1), new anchor.BN(1_000_000), encodeMetadataUri(""), new anchor.BN(0), noLimits)
+        .accounts({
+          globalState: globalStatePda,
+          timeslot: timeslotPda,
+          roundIndex: roundIndexPda,
+          authority: authority.publicKey,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .rpc();
+
+      const sellerBefore = (await getAccount(provider.connection, sellerEnergyAta)).amount;
+      await program.methods
+        .commitSupply(withdrawEpoch, reservePrice, new anchor.BN(4))
+        .accounts(commitAccounts())
+        .signers([seller])
+        .rpc();
+
+      await program.methods
+        .withdrawSupply()
+        .accounts(withdrawAccounts())
+        .signers([seller])
+        .rpc();
+
+      const sellerAfter = (await getAccount(provider.connection, sellerEnergyAta)).amount;
+      assert.equal(sellerAfter.toString(), sellerBefore.toString(), "energy returned");
+      assert.isNull(await provider.connection.getAccountInfo(supplyPda), "supply closed");
+      assert.isNull(await provider.connection.getAccountInfo(sellerEscrowPda), "escrow closed");
+
+      const ts = await program.account.timeslot.fetch(timeslotPda);
+      assert.equal(ts.totalSupply.toNumber(), 0);
+      const level = await program.account.supplyLevelAggregate.fetch(supplyLevelPda);
+      assert.equal(level.totalQuantity.toNumber(), 0);
+      assert.equal(level.sellerCount, 0);
+    });
+
+    it("✅ Withdraws even when extra tokens were sent to the escrow", async () => {
+      await program.methods
+        .commitSupply(withdrawEpoch, reservePrice, new anchor.BN(4))
+        .accounts(commitAccounts())
+        .signers([seller])
+        .rpc();
+      // anyone can transfer into the escrow; that must not lock the seller out
+      await mintTo(
+        provider.connection,
+        authority.payer,
+        energyMint.publicKey,
+        sellerEscrowPda,
+        authority.publicKey,
+        1
+      );
+
+      const sellerBefore = (await getAccount(provider.connection, sellerEnergyAta)).amount;
+      await program.methods
+        .withdrawSupply()
+        .accounts(withdrawAccounts())
+        .signers([seller])
+        .rpc();
+
+      const sellerAfter = (await getAccount(provider.connection, sellerEnergyAta)).amount;
+      assert.equal((sellerAfter - sellerBefore).toString(), "5", "escrow swept in full");
+      assert.isNull(await provider.connection.getAccountInfo(sellerEscrowPda), "escrow closed");
+      const ts = await program.account.timeslot.fetch(timeslotPda);
+      assert.equal(ts.totalSupply.toNumber(), 0);
+    });
+
+    it("🚫 Fails to withdraw supply after the timeslot is sealed", async () => {
+      // the seller can commit again after withdrawing
+      await program.methods
+        .commitSupply(withdrawEpoch, reservePrice, new anchor.BN(4))
+        .accounts(commitAccounts())
+        .signers([seller])
+        .rpc();
+
+      await program.methods
+        .sealTimeslot()
+        .accounts({
+          globalState: globalStatePda,
+          timeslot: timeslotPda,
+          authority: authority.publicKey,
+        })
+        .rpc();
+
+      try {
+        await program.methods
+          .withdrawSupply()
+          .accounts(withdrawAccounts())
+          .signers([seller])
+          .rpc();
+        assert.fail("Expected withdrawal from a sealed timeslot to fail");
+      } catch (err) {
+        assert.instanceOf(err, AnchorError);
+        assert.equal((err as AnchorError).error.errorCode.code, "InvalidTimeslot");
+      }
+
+      const ts = await program.account.timeslot.fetch(timeslotPda);
+      assert.equal(ts.totalSupply.toNumber(), 4);
+    });
+  });
+
+  describe("Multi-seller Proceeds", () => {
+    it("✅ Pays each seller proceeds proportional to their supply", async () => {
+      const proRataEpoch = new anchor.BN(Date.now() + 210_000);
+      const [timeslotPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("timeslot"), proRataEpoch.toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      const [timeslotQuoteEscrow] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("quote_escrow"), timeslotPda.toBuffer()],
+        program.programId
+      );
+      const reservePrice = new anchor.BN(1_000_000);
+      const clearing = new anchor.BN(3_000_000);
+      const [supplyLevelPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("supply_level"), timeslotPda.toBuffer(), reservePrice.toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+
+      const otherSeller = anchor.web3.Keypair.generate();
+      await airdropAndConfirm(otherSeller.publicKey, 1 * anchor.web3.LAMPORTS_PER_SOL);
+      const otherEnergyAta = await createAssociatedTokenAccount(
+        provider.connection,
+        otherSeller,
+        energyMint.publicKey,
+        otherSeller.publicKey
+      );
+      const otherQuoteAta = await createAssociatedTokenAccount(
+        provider.connection,
+        otherSeller,
+        quoteMint.publicKey,
+        otherSeller.publicKey
+      );
+      await mintTo(
+        provider.connection,
+        authority.payer,
+        energyMint.publicKey,
+        otherEnergyAta,
+        authority.publicKey,
+        4
+      );
+
+      const { pda: roundIndexPda } = await deriveNextRoundPda();
+      await program.methods
+        .openTimeslot(proRataEpoch, new anchor.BN(1), new anchor.BN(1_000_000), encodeMetadataUri(""), new anchor.BN(0), noLimits)
+        .accounts({
+          globalState: globalStatePda,
+          timeslot: timeslotPda,
+          roundIndex: roundIndexPda,
+          authority: authority.publicKey,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .rpc();
+
+      // 6 lots from the main seller and 4 from the other, same reserve
+      const sellers = [
+        { kp: seller, energyAta: sellerEnergyAta, quoteAta: sellerQuoteAta, amount: 6 },
+        { kp: otherSeller, energyAta: otherEnergyAta, quoteAta: otherQuoteAta, amount: 4 },
+      ].map((s) => {
+        const [supplyPda] = anchor.web3.PublicKey.findProgramAddressSync(
+          [Buffer.from("supply"), timeslotPda.toBuffer(), s.kp.publicKey.toBuffer()],
+          program.programId
+        );
+        const [sellerEscrowPda] = anchor.web3.PublicKey.findProgramAddressSync(
+          [Buffer.from("seller_escrow"), timeslotPda.toBuffer(), s.kp.publicKey.toBuffer()],
+          program.programId
+        );
+        return { ...s, supplyPda, sellerEscrowPda };
+      });
+
+      for (const s of sellers) {
+        await program.methods
+          .commitSupply(proRataEpoch, reservePrice, new anchor.BN(s.amount))
+          .accounts({
+            globalState: globalStatePda,
+            timeslot: timeslotPda,
+            supply: s.supplyPda,
+            supplyLevel: supplyLevelPda,
+            energyMint: energyMint.publicKey,
+            sellerSource: s.energyAta,
+            sellerEscrow: s.sellerEscrowPda,
+            signer: s.kp.publicKey,
+            systemProgram: anchor.web3.SystemProgram.programId,
+            tokenProgram: TOKEN_PROGRAM_ID,
+          })
+          .signers([s.kp])
+          .rpc();
+      }
+
+      const { pda: bidPagePda, pageIndex } = await deriveBidPagePda(timeslotPda);
+      await program.methods
+        .placeBid(pageIndex, clearing, new anchor.BN(5), new anchor.BN(Date.now()), new anchor.BN(0))
+        .accounts({
+          globalState: globalStatePda,
+          timeslot: timeslotPda,
+          timeslotQuoteEscrow,
+          quoteMint: quoteMint.publicKey,
+          buyerSource: buyerQuoteAta,
+          buyer: buyer.publicKey,
+          bidPage: bidPagePda,
+          demandLevel: deriveDemandLevelPda(timeslotPda, clearing),
+          systemProgram: anchor.web3.SystemProgram.programId,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([buyer])
+        .rpc();
+
+      await program.methods
+        .sealTimeslot()
+        .accounts({
+          globalState: globalStatePda,
+          timeslot: timeslotPda,
+          authority: authority.publicKey,
+        })
+        .rpc();
+
+      await program.methods
+        .executeAuctionClearing(1)
+        .accounts({
+          globalState: globalStatePda,
+          timeslot: timeslotPda,
+          authority: authority.publicKey,
+        })
+        .remainingAccounts([
+          { pubkey: supplyLevelPda, isSigner: false, isWritable: false },
+          { pubkey: deriveDemandLevelPda(timeslotPda, clearing), isSigner: false, isWritable: false },
+        ])
+        .rpc();
+
+      const slot = await program.account.timeslot.fetch(timeslotPda);
+      assert.equal(slot.totalSoldQuantity.toNumber(), 5);
+      assert.equal(slot.clearedSupply.toNumber(), 10);
+
+      const globalState = await program.account.globalState.fetch(globalStatePda);
+      const feeVaultBefore = (await getAccount(provider.connection, feeVaultPda)).amount;
+      let combinedFee = new anchor.BN(0);
+
+      // 5 sold over 10 lots: 3 for the 6-lot seller, 2 for the 4-lot seller
+      for (const [s, expectedLots] of [[sellers[0], 3], [sellers[1], 2]] as const) {
+        const gross = clearing.mul(new anchor.BN(expectedLots));
+        const fee = gross.mul(new anchor.BN(globalState.feeBps)).div(new anchor.BN(10000));
+        combinedFee = combinedFee.add(fee);
+
+        const before = (await getAccount(provider.connection, s.quoteAta)).amount;
+        await program.methods
+          .withdrawProceeds()
+          .accounts({
+            globalState: globalStatePda,
+            timeslot: timeslotPda,
+            supply: s.supplyPda,
+            timeslotQuoteEscrow,
+            feeVault: feeVaultPda,
+            sellerProceedsAta: s.quoteAta,
+            seller: s.kp.publicKey,
+            tokenProgram: TOKEN_PROGRAM_ID,
+          })
+          .signers([s.kp])
+          .rpc();
+        const after = (await getAccount(provider.connection, s.quoteAta)).amount;
+        assert.equal((after - before).toString(), gross.sub(fee).toString());
+      }
+
+      const feeVaultAfter = (await getAccount(provider.connection, feeVaultPda)).amount;
+      assert.equal((feeVaultAfter - feeVaultBefore).toString(), combinedFee.toString());
+    });
+  });
+
+  // Shutdown is global and irreversible, so this block must stay last.
+  describe("Protocol Shutdown", () => {
+    const settledEpoch = new anchor.BN(Date.now() + 360_000);
+    const openEpoch = new anchor.BN(Date.now() + 390_000);
+    const reservePrice = new anchor.BN(1_000_000);
+    const bidPrice = new anchor.BN(3_000_000);
+    const clearing = new anchor.BN(2_000_000);
+    const quantity = new anchor.BN(4);
+
+    const timeslotPdas = (epoch: anchor.BN) => {
+      const [timeslotPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("timeslot"), epoch.toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      const [timeslotQuoteEscrow] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("quote_escrow"), timeslotPda.toBuffer()],
+        program.programId
+      );
+      const [supplyPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("supply"), timeslotPda.toBuffer(), seller.publicKey.toBuffer()],
+        program.programId
+      );
+      const [sellerEscrowPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("seller_escrow"), timeslotPda.toBuffer(), seller.publicKey.toBuffer()],
+        program.programId
+      );
+      const [supplyLevelPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("supply_level"), timeslotPda.toBuffer(), reservePrice.toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+      const [fillReceiptPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("fill_receipt"), timeslotPda.toBuffer(), buyer.publicKey.toBuffer()],
+        program.programId
+      );
+      return { timeslotPda, timeslotQuoteEscrow, supplyPda, sellerEscrowPda, supplyLevelPda, fillReceiptPda };
+    };
+    const settled = timeslotPdas(settledEpoch);
+    const open = timeslotPdas(openEpoch);
+
+    const openSlot = async (epoch: anchor.BN, timeslotPda: anchor.web3.PublicKey) => {
+      const { pda: roundIndexPda } = await deriveNextRoundPda();
+      await program.methods
+        .openTimeslot(epoch, new anchor.BN(1), new anchor.BN(1_000_000), encodeMetadataUri(""), new anchor.BN(0), noLimits)
+        .accounts({
+          globalState: globalStatePda,
+          timeslot: timeslotPda,
+          roundIndex: roundIndexPda,
+          authority: authority.publicKey,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .rpc();
+    };
+
+    const commit = (epoch: anchor.BN, pdas: ReturnType<typeof timeslotPdas>) =>
+      program.methods
+        .commitSupply(epoch, reservePrice, quantity)
+        .accounts({
+          globalState: globalStatePda,
+          timeslot: pdas.timeslotPda,
+          supply: pdas.supplyPda,
+          supplyLevel: pdas.supplyLevelPda,
+          energyMint: energyMint.publicKey,
+          sellerSource: sellerEnergyAta,
+          sellerEscrow: pdas.sellerEscrowPda,
+          signer: seller.publicKey,
+          systemProgram: anchor.web3.SystemProgram.programId,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([seller])
+        .rpc();
+
+    const bid = async (pdas: ReturnType<typeof timeslotPdas>) => {
+      const { pda: bidPagePda, pageIndex } = await deriveBidPagePda(pdas.timeslotPda);
+      return program.methods
+        .placeBid(pageIndex, bidPrice, quantity, new anchor.BN(Date.now()), new anchor.BN(0))
+        .accounts({
+          globalState: globalStatePda,
+          timeslot: pdas.timeslotPda,
+          timeslotQuoteEscrow: pdas.timeslotQuoteEscrow,
+          quoteMint: quoteMint.publicKey,
+          buyerSource: buyerQuoteAta,
+          buyer: buyer.publicKey,
+          bidPage: bidPagePda,
+          demandLevel: deriveDemandLevelPda(pdas.timeslotPda, bidPrice),
+          systemProgram: anchor.web3.SystemProgram.programId,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([buyer])
+        .rpc();
+    };
+
+    const expectShutdown = async (call: () => Promise<unknown>) => {
+      try {
+        await call();
+        assert.fail("Expected the call to be rejected after shutdown");
+      } catch (err) {
+        assert.instanceOf(err, AnchorError);
+        assert.equal((err as AnchorError).error.errorCode.code, "ProtocolShutdown");
+      }
+    };
+
+    it("✅ Shuts the protocol down with a settled timeslot still unpaid", async () => {
+      // a settled timeslot whose seller and buyer have not been paid yet
+      await openSlot(settledEpoch, settled.timeslotPda);
+      await commit(settledEpoch, settled);
+      await bid(settled);
+      await program.methods
+        .sealTimeslot()
+        .accounts({ globalState: globalStatePda, timeslot: settled.timeslotPda, authority: authority.publicKey })
+        .rpc();
+      await program.methods
+        .settleTimeslot(clearing, quantity)
+        .accounts({ globalState: globalStatePda, timeslot: settled.timeslotPda, authority: authority.publicKey })
+        .remainingAccounts([{ pubkey: settled.supplyLevelPda, isSigner: false, isWritable: false }])
+        .rpc();
+      await program.methods
+        .createFillReceipt(quantity)
+        .accounts({
+          globalState: globalStatePda,
+          timeslot: settled.timeslotPda,
+          buyer: buyer.publicKey,
+          fillReceipt: settled.fillReceiptPda,
+          authority: authority.publicKey,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .rpc();
+
+      // and an Open timeslot that new supply and bids would target
+      await openSlot(openEpoch, open.timeslotPda);
+
+      await program.methods
+        .protocolShutdown()
+        .accounts({ globalState: globalStatePda, authority: authority.publicKey })
+        .rpc();
+
+      const config = await program.methods
+        .getProtocolConfig()
+        .accounts({ globalState: globalStatePda })
+        .view();
+      assert.isTrue(config.shutdown);
+    });
+
+    it("🚫 Rejects opening a timeslot after shutdown", async () => {
+      const epoch = new anchor.BN(Date.now() + 420_000);
+      await expectShutdown(() => openSlot(epoch, timeslotPdas(epoch).timeslotPda));
+    });
+
+    it("🚫 Rejects committing supply after shutdown", async () => {
+      await expectShutdown(() => commit(openEpoch, open));
+    });
+
+    it("🚫 Rejects placing a bid after shutdown", async () => {
+      await expectShutdown(() => bid(open));
+    });
+
+    it("✅ Still lets the seller withdraw proceeds after shutdown", async () => {
+      const globalState = await program.account.globalState.fetch(globalStatePda);
+      const gross = clearing.mul(quantity);
+      const fee = gross.mul(new anchor.BN(globalState.feeBps)).div(new anchor.BN(10000));
+
+      const before = (await getAccount(provider.connection, sellerQuoteAta)).amount;
+      await program.methods
+        .withdrawProceeds()
+        .accounts({
+          globalState: globalStatePda,
+          timeslot: settled.timeslotPda,
+          supply: settled.supplyPda,
+          timeslotQuoteEscrow: settled.timeslotQuoteEscrow,
+          feeVault: feeVaultPda,
+          sellerProceedsAta: sellerQuoteAta,
+          seller: seller.publicKey,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([seller])
+        .rpc();
+      const after = (await getAccount(provider.connection, sellerQuoteAta)).amount;
+      assert.equal((after - before).toString(), gross.sub(fee).toString());
+    });
+
+    it("✅ Still lets the buyer redeem energy and a refund after shutdown", async () => {
+      const escrowed = bidPrice.mul(quantity);
+      const expectedRefund = escrowed.sub(clearing.mul(quantity));
+
+      const quoteBefore = (await getAccount(provider.connection, buyerQuoteAta)).amount;
+      const energyBefore = (await getAccount(provider.connection, buyerEnergyAta)).amount;
+      await program.methods
+        .redeemEnergyAndRefund(escrowed)
+        .accounts({
+          globalState: globalStatePda,
+          timeslot: settled.timeslotPda,
+          fillReceipt: settled.fillReceiptPda,
+          timeslotQuoteEscrow: settled.timeslotQuoteEscrow,
+          buyerQuoteAta: buyerQuoteAta,
+          buyerEnergyAta: buyerEnergyAta,
+          supply: settled.supplyPda,
+          sellerEscrow: settled.sellerEscrowPda,
+          buyer: buyer.publicKey,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([buyer])
+        .rpc();
+      const quoteAfter = (await getAccount(provider.connection, buyerQuoteAta)).amount;
+      const energyAfter = (await getAccount(provider.connection, buyerEnergyAta)).amount;
+      assert.equal((quoteAfter - quoteBefore).toString(), expectedRefund.toString());
+      assert.equal((energyAfter - energyBefore).toString(), quantity.toString());
+    });
   });
 });